@@ -0,0 +1,39 @@
+//! Setup shared by the benchmarks.
+
+use octa_force::glam::{ivec2, IVec2};
+use reload::grid::{get_node_index_from_pos, Grid, GRID_SIZE};
+use reload::rule_gen::gen_rules_from_grid;
+use reload::rule_gen::neighborhood::Neighborhood;
+use reload::rule_gen::RuleGenSettings;
+use reload::rules::Rule;
+use reload::value::Value;
+
+/// Checkerboard of 2x2 blocks.
+pub fn pattern_grid() -> Grid {
+    let mut grid = Grid::new(Value::from_value_nr(0));
+    for x in 0..GRID_SIZE as i32 {
+        for y in 0..GRID_SIZE as i32 {
+            let nr = ((x / 2 + y / 2) % 2) as u8;
+            grid.nodes[get_node_index_from_pos(ivec2(x, y))] = Value::from_value_nr(nr);
+        }
+    }
+    grid
+}
+
+/// Rules of the Moore neighborhoods of the grid.
+pub fn pattern_rules(grid: &Grid) -> Vec<Rule> {
+    let settings = RuleGenSettings::new(Neighborhood::Moore.get_offsets());
+    gen_rules_from_grid(grid, 2, &settings).unwrap()
+}
+
+/// Flips random nodes of the pattern to the other value.
+pub fn random_edits(grid: &Grid, num_edits: usize, seed: u64) -> Vec<(IVec2, Value)> {
+    let mut rng = fastrand::Rng::with_seed(seed);
+    (0..num_edits)
+        .map(|_| {
+            let pos = ivec2(rng.i32(0..GRID_SIZE as i32), rng.i32(0..GRID_SIZE as i32));
+            let value = grid.nodes[get_node_index_from_pos(pos)].get_value_nr();
+            (pos, Value::from_value_nr(1 - value))
+        })
+        .collect()
+}
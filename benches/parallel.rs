@@ -0,0 +1,52 @@
+//! Measures the speedup of spreading the parallel expansions of a repair over more threads.
+//! The number of expansions per tick is fixed, so every run does the same search and only the thread count changes.
+//! Run with `cargo bench --bench parallel`.
+
+mod common;
+
+use std::time::Duration;
+use reload::grid::GRID_SIZE;
+use reload::solve::{solve, SolveOptions, SolveResult};
+use common::{pattern_grid, pattern_rules, random_edits};
+
+const SEED: u64 = 42;
+const RUNS: u32 = 5;
+const NUM_EDITS: usize = 6;
+const PARALLEL_EXPANSIONS: usize = 16;
+const NUM_THREADS: [usize; 4] = [1, 2, 4, 8];
+
+fn main() {
+    let grid = pattern_grid();
+    let rules = pattern_rules(&grid);
+    let edits = random_edits(&grid, NUM_EDITS, SEED);
+
+    println!("{GRID_SIZE}x{GRID_SIZE} grid, {NUM_EDITS} edits, K = {PARALLEL_EXPANSIONS}, {} cores",
+             std::thread::available_parallelism().map_or(1, |n| n.get()));
+
+    let mut serial: Option<(Duration, SolveResult)> = None;
+    for num_threads in NUM_THREADS {
+        let options = SolveOptions::new()
+            .with_seed(SEED)
+            .with_parallel_expansions(PARALLEL_EXPANSIONS)
+            .with_num_threads(num_threads);
+
+        let mut time = Duration::ZERO;
+        let mut result = None;
+        for _ in 0..RUNS {
+            let run = solve(grid, rules.to_owned(), &edits, &options).unwrap();
+            time += run.elapsed;
+            result = Some(run);
+        }
+        time /= RUNS;
+        let result = result.unwrap();
+
+        let (serial_time, serial_result) = serial.get_or_insert((time, result.to_owned()));
+        let same_search = result.stats.ticks == serial_result.stats.ticks
+            && result.grid.nodes == serial_result.grid.nodes;
+
+        println!("{num_threads:>2} threads: {time:>10.2?} {:>8} ticks {:?}, speedup {:.2}x, same search {same_search}",
+                 result.stats.ticks,
+                 result.status,
+                 serial_time.as_secs_f64() / time.as_secs_f64());
+    }
+}
@@ -0,0 +1,42 @@
+//! Compares the lazy repair against the classic WFC on the same rules and edits.
+//! Run with `cargo bench --bench solvers`.
+
+mod common;
+
+use std::time::Duration;
+use reload::grid::GRID_SIZE;
+use reload::solver::{run_solver, SolverKind};
+use common::{pattern_grid, pattern_rules, random_edits};
+
+const SEED: u64 = 42;
+const RUNS: u32 = 5;
+const NUM_EDITS: usize = 6;
+const MAX_STEPS: usize = 1_000_000;
+
+fn main() {
+    let grid = pattern_grid();
+    let rules = pattern_rules(&grid);
+    let edits = random_edits(&grid, NUM_EDITS, SEED);
+
+    println!("{GRID_SIZE}x{GRID_SIZE} grid, {NUM_EDITS} edits");
+
+    for kind in SolverKind::ALL {
+        let mut time = Duration::ZERO;
+        let mut results = vec![];
+        for run in 0..RUNS {
+            let mut solver = kind.create(grid, rules.to_owned(), SEED + run as u64);
+            time += run_solver(solver.as_mut(), &edits, Some(MAX_STEPS)).unwrap();
+
+            let changed = solver.get_result().nodes.iter()
+                .zip(grid.nodes.iter())
+                .filter(|(a, b)| a.color_index != b.color_index)
+                .count();
+            results.push((solver.get_status(), solver.get_stats().ticks, solver.get_stats().restarts, changed));
+        }
+        time /= RUNS;
+
+        let (status, steps, restarts, changed) = results[0];
+        println!("{:<12} {time:>10.2?} {steps:>8} steps {restarts:>4} restarts {changed:>5} changed nodes {status:?}",
+                 kind.get_name());
+    }
+}
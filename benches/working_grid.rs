@@ -0,0 +1,97 @@
+//! Compares branching working grids by copying them against the copy-on-write `ChunkedGrid` and shared `OrderQueue`.
+//! Both variants run the same repair through `GridManager::expand_working_grid`, the copying one gives every new
+//! branch its own copy of both grids and the orders before it is queued, like `to_owned` on plain grids did.
+//! Run with `cargo bench --bench working_grid --features grid-128`.
+
+mod common;
+
+use std::time::{Duration, Instant};
+use fastrand::Rng;
+use reload::chunked_grid::ChunkedGrid;
+use reload::grid::GRID_SIZE;
+use reload::grid_manager::{GridManager, SolverStats, WorkingGrid};
+use reload::order_queue::OrderQueue;
+use common::{pattern_grid, pattern_rules, random_edits};
+
+const SEED: u64 = 42;
+const NUM_EDITS: usize = 3;
+const MAX_TICKS: usize = 100_000;
+
+struct Run {
+    time: Duration,
+    ticks: usize,
+    peak_queue_size: usize,
+    peak_bytes: usize,
+    solution: Option<WorkingGrid>,
+}
+
+fn copy_branch(mut working_grid: WorkingGrid) -> WorkingGrid {
+    working_grid.full_grid = ChunkedGrid::from(&working_grid.full_grid.to_grid());
+    working_grid.empty_grid = ChunkedGrid::from(&working_grid.empty_grid.to_grid());
+
+    let mut orders = working_grid.orders.to_owned();
+    working_grid.orders = OrderQueue::new();
+    while let Some(order) = orders.pop_front() {
+        working_grid.orders.push_back(order);
+    }
+
+    working_grid
+}
+
+/// Ticks the repair like `GridManager::tick` until the first solution. Only the expansions and queueing are timed.
+fn run(copy: bool) -> Run {
+    let grid = pattern_grid();
+    let mut grid_manager = GridManager::new(grid, pattern_rules(&grid)).with_seed(SEED);
+    grid_manager.select_values(&random_edits(&grid, NUM_EDITS, SEED)).unwrap();
+
+    let mut rng = Rng::with_seed(SEED);
+    let mut stats = SolverStats::default();
+    let mut run = Run { time: Duration::ZERO, ticks: 0, peak_queue_size: 0, peak_bytes: 0, solution: None };
+    while run.solution.is_none() && run.ticks < MAX_TICKS {
+        let start = Instant::now();
+        let Some(mut working_grid) = grid_manager.working_grids.pop_front() else {
+            break
+        };
+        run.ticks += 1;
+
+        let Some((pos, satisfied)) = working_grid.orders.pop_front() else {
+            run.solution = Some(working_grid);
+            break
+        };
+
+        for new_grid in grid_manager.expand_working_grid(working_grid, pos, satisfied, &mut rng, &mut stats) {
+            let new_grid = if copy { copy_branch(new_grid) } else { new_grid };
+            if new_grid.orders.is_empty() {
+                run.solution = Some(new_grid);
+                break
+            }
+            grid_manager.insert_working_grid(new_grid);
+        }
+        run.time += start.elapsed();
+
+        if grid_manager.working_grids.len() > run.peak_queue_size {
+            run.peak_queue_size = grid_manager.working_grids.len();
+            run.peak_bytes = grid_manager.get_working_grid_bytes();
+        }
+    }
+
+    run
+}
+
+fn main() {
+    let copy = run(true);
+    let cow = run(false);
+
+    let same_repair = copy.ticks == cow.ticks && match (&copy.solution, &cow.solution) {
+        (Some(a), Some(b)) => a.full_grid.to_grid().nodes == b.full_grid.to_grid().nodes,
+        _ => false,
+    };
+
+    println!("{GRID_SIZE}x{GRID_SIZE} grid, {NUM_EDITS} edits, {} ticks, peak queue {} working grids, same repair {same_repair}",
+             cow.ticks, cow.peak_queue_size);
+    println!("copy:          {:>10.2?} {:>10} KiB", copy.time, copy.peak_bytes / 1024);
+    println!("copy-on-write: {:>10.2?} {:>10} KiB", cow.time, cow.peak_bytes / 1024);
+    println!("speedup {:.1}x, memory {:.1}x smaller",
+             copy.time.as_secs_f64() / cow.time.as_secs_f64(),
+             copy.peak_bytes as f64 / cow.peak_bytes.max(1) as f64);
+}
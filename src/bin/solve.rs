@@ -0,0 +1,125 @@
+use std::env;
+use std::time::Duration;
+use octa_force::anyhow::{anyhow, bail};
+use octa_force::glam::ivec2;
+use octa_force::OctaResult;
+use reload::budget::SolveBudget;
+use reload::grid::{get_node_index_from_pos, Grid, GRID_SIZE};
+use reload::grid_manager::{SolutionRanking, SolveStatus};
+use reload::rule_gen::config::RuleGenConfig;
+use reload::solve::{solve, SolveOptions};
+use reload::solver::{run_solver, SolverKind};
+use reload::value::{Value, ValueNr};
+
+const USAGE: &str = "usage: solve <rule_gen.cfg> [--seed n] [--max-ticks n] [--time-ms n] [--solutions n] [--beam n] [--parallel n] [--threads n] [--diagnose] [--solver lazy|wfc] [--image out.png] <x,y=value>...";
+
+/// Repairs an empty grid after the given edits with the rules of a rule gen config and prints the result.
+/// With `--image` the result is also drawn like the model of the config draws it, tiles for a tileset.
+fn main() -> OctaResult<()> {
+    let mut args = env::args().skip(1);
+    let config_path = args.next().ok_or(anyhow!("{USAGE}"))?;
+    let config = RuleGenConfig::load(&config_path)?;
+    let (rules, _, model_output) = config.gen_model()?;
+
+    let mut options = SolveOptions::new();
+    let mut budget = SolveBudget::default();
+    let mut solver_kind = SolverKind::default();
+    let mut edits = vec![];
+    let mut image_path = None;
+    let mut lazy_flags = vec![];
+    while let Some(arg) = args.next() {
+        if matches!(arg.as_str(), "--time-ms" | "--beam" | "--parallel" | "--threads" | "--diagnose" | "--solutions") {
+            lazy_flags.push(arg.to_owned());
+        }
+
+        let mut next_number = || -> OctaResult<u64> {
+            let value = args.next().ok_or(anyhow!("{arg} needs a value"))?;
+            Ok(value.parse()?)
+        };
+
+        match arg.as_str() {
+            "--seed" => options = options.with_seed(next_number()?),
+            "--max-ticks" => budget = budget.with_max_ticks(next_number()? as usize),
+            "--time-ms" => budget = budget.with_time_limit(Duration::from_millis(next_number()?)),
+            "--beam" => options = options.with_beam_width(next_number()? as usize),
+            "--parallel" => options = options.with_parallel_expansions(next_number()? as usize),
+            "--threads" => options = options.with_num_threads(next_number()? as usize),
+            "--diagnose" => options = options.with_diagnose_failures(true),
+            "--image" => image_path = Some(args.next().ok_or(anyhow!("{arg} needs a value"))?),
+            "--solver" => solver_kind = args.next().ok_or(anyhow!("{arg} needs a value"))?.parse()?,
+            "--solutions" => options = options.with_solutions(next_number()? as usize, SolutionRanking::Score),
+            _ => {
+                let (pos, value) = arg.split_once('=').ok_or(anyhow!("{USAGE}"))?;
+                let (x, y) = pos.split_once(',').ok_or(anyhow!("{USAGE}"))?;
+                let value_nr: ValueNr = value.trim().parse()?;
+                if value_nr as usize >= rules.len() {
+                    bail!("Value {value_nr} has no rule, there are only {} values", rules.len());
+                }
+                edits.push((ivec2(x.trim().parse()?, y.trim().parse()?), Value::from_value_nr(value_nr)));
+            }
+        }
+    }
+    if edits.is_empty() {
+        bail!("{USAGE}");
+    }
+    options = options.with_budget(budget);
+
+    let grid = Grid::new(Value::from_value_nr(0));
+    if solver_kind != SolverKind::LazyRepair {
+        if !lazy_flags.is_empty() {
+            bail!("{} only work with the lazy solver", lazy_flags.join(", "));
+        }
+        
+        let seed = options.seed.unwrap_or(fastrand::u64(..));
+        let mut solver = solver_kind.create(grid, rules, seed);
+        let elapsed = run_solver(solver.as_mut(), &edits, budget.max_ticks)?;
+
+        println!("Solver: {}", solver.get_name());
+        println!("Status: {:?}", solver.get_status());
+        println!("Seed: {seed}");
+        println!("Steps: {}", solver.get_stats().ticks);
+        println!("Restarts: {}", solver.get_stats().restarts);
+        println!("Elapsed: {elapsed:?}");
+        print_grid(solver.get_result());
+        if let Some(image_path) = &image_path {
+            model_output.grid_to_image(solver.get_result()).save(image_path)?;
+        }
+
+        return Ok(());
+    }
+
+    let result = solve(grid, rules, &edits, &options)?;
+
+    println!("Status: {:?}", result.status);
+    println!("Seed: {}", result.seed);
+    println!("Ticks: {}", result.stats.ticks);
+    println!("Peak queue size: {}", result.stats.peak_queue_size);
+    println!("Branches: {} created, {} died ({} on locked nodes), {} pruned",
+        result.stats.branches_created, result.stats.branches_died, result.stats.lock_conflicts, result.stats.get_num_pruned());
+    println!("Solutions: {}", result.solutions.len());
+    println!("Elapsed: {:?}", result.elapsed);
+
+    if let Some(diagnosis) = &result.diagnosis {
+        print!("{diagnosis}");
+    }
+
+    let grid = match result.status {
+        SolveStatus::BudgetExceeded(_) => result.best_partial.unwrap_or(result.grid),
+        _ => result.grid,
+    };
+    print_grid(&grid);
+    if let Some(image_path) = &image_path {
+        model_output.grid_to_image(&grid).save(image_path)?;
+    }
+
+    Ok(())
+}
+
+fn print_grid(grid: &Grid) {
+    for y in 0..GRID_SIZE as i32 {
+        let row: Vec<_> = (0..GRID_SIZE as i32)
+            .map(|x| grid.nodes[get_node_index_from_pos(ivec2(x, y))].get_value_nr().to_string())
+            .collect();
+        println!("{}", row.join(" "));
+    }
+}
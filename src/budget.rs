@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+use std::mem;
+use std::time::{Duration, Instant};
+use crate::chunked_grid::Chunk;
+use crate::grid_manager::{GridManager, SolveStatus, WorkingGrid};
+use crate::order_queue::Order;
+
+pub const BYTES_CHECK_INTERVAL: usize = 64;
+
+/// Limits of a single repair. When one is hit the repair ends with `SolveStatus::BudgetExceeded`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct SolveBudget {
+    pub max_ticks: Option<usize>,
+    pub time_limit: Option<Duration>,
+    pub max_working_grids: Option<usize>,
+    /// Estimated memory of all queued working grids. Chunks shared between working grids are counted once.
+    /// Counting walks the whole queue, so it is only checked every `BYTES_CHECK_INTERVAL` ticks.
+    pub max_bytes: Option<usize>,
+}
+
+/// The limit of the `SolveBudget` that ended a repair.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum BudgetLimit {
+    Ticks,
+    Time,
+    WorkingGrids,
+    Bytes,
+}
+
+impl SolveBudget {
+    pub fn with_max_ticks(mut self, max_ticks: usize) -> Self {
+        self.max_ticks = Some(max_ticks);
+        self
+    }
+
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn with_max_working_grids(mut self, max_working_grids: usize) -> Self {
+        self.max_working_grids = Some(max_working_grids);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+}
+
+impl GridManager {
+    pub(crate) fn get_exceeded_limit(&self, start: Instant) -> Option<BudgetLimit> {
+        let budget = &self.budget;
+
+        if budget.max_ticks.is_some_and(|max| self.stats.ticks >= max) {
+            Some(BudgetLimit::Ticks)
+        } else if budget.time_limit.is_some_and(|limit| start.elapsed() >= limit) {
+            Some(BudgetLimit::Time)
+        } else if budget.max_working_grids.is_some_and(|max| self.working_grids.len() > max) {
+            Some(BudgetLimit::WorkingGrids)
+        } else if budget.max_bytes.is_some_and(|max| self.is_bytes_check_due() && self.get_working_grid_bytes() > max) {
+            Some(BudgetLimit::Bytes)
+        } else {
+            None
+        }
+    }
+
+    /// Ends the repair. Solutions found so far still count, otherwise the best queued working grid is kept as partial result.
+    pub(crate) fn exceed_budget(&mut self, limit: BudgetLimit) {
+        if !self.done_grids.is_empty() {
+            self.grid = self.done_grids[0];
+            self.working_grids.clear();
+            self.status = SolveStatus::Solved;
+            return;
+        }
+        
+        self.best_partial = self.working_grids.front().map(|working_grid| working_grid.full_grid.to_grid());
+        self.working_grids.clear();
+        self.status = SolveStatus::BudgetExceeded(limit);
+    }
+
+    /// A tick advances the tick count by up to `parallel_expansions`, so no multiple of the interval is skipped.
+    fn is_bytes_check_due(&self) -> bool {
+        self.stats.ticks % BYTES_CHECK_INTERVAL < self.parallel_expansions.max(1)
+    }
+
+    pub fn get_working_grid_bytes(&self) -> usize {
+        let mut chunks = HashSet::new();
+        let mut bytes = 0;
+        for working_grid in self.working_grids.iter() {
+            chunks.extend(working_grid.full_grid.get_chunk_ptrs());
+            chunks.extend(working_grid.empty_grid.get_chunk_ptrs());
+            bytes += mem::size_of::<WorkingGrid>() + working_grid.orders.len() * mem::size_of::<Order>();
+        }
+
+        bytes + chunks.len() * mem::size_of::<Chunk>()
+    }
+}
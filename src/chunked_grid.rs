@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use crate::grid::{Grid, NodeIndex, NODES_PER_GRID};
+use crate::value::Value;
+
+pub const NODES_PER_CHUNK: usize = 64;
+
+pub type Chunk = [Value; NODES_PER_CHUNK];
+
+/// Copy-on-write node storage. Clones share all chunks, a chunk is only copied when a shared one is written to.
+#[derive(Clone)]
+pub struct ChunkedGrid {
+    chunks: Vec<Arc<Chunk>>,
+    num_nodes: usize,
+}
+
+impl ChunkedGrid {
+    pub fn new(num_nodes: usize, value: Value) -> Self {
+        let chunk = Arc::new([value; NODES_PER_CHUNK]);
+        ChunkedGrid {
+            chunks: vec![chunk; num_nodes.div_ceil(NODES_PER_CHUNK)],
+            num_nodes,
+        }
+    }
+    
+    pub fn from_nodes(nodes: &[Value]) -> Self {
+        ChunkedGrid {
+            chunks: nodes.chunks(NODES_PER_CHUNK)
+                .map(|nodes| {
+                    let mut chunk = [Value::default(); NODES_PER_CHUNK];
+                    chunk[..nodes.len()].copy_from_slice(nodes);
+                    Arc::new(chunk)
+                })
+                .collect(),
+            num_nodes: nodes.len(),
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.num_nodes
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.num_nodes == 0
+    }
+    
+    pub fn get(&self, index: NodeIndex) -> Value {
+        self.chunks[index / NODES_PER_CHUNK][index % NODES_PER_CHUNK]
+    }
+    
+    pub fn get_mut(&mut self, index: NodeIndex) -> &mut Value {
+        &mut Arc::make_mut(&mut self.chunks[index / NODES_PER_CHUNK])[index % NODES_PER_CHUNK]
+    }
+    
+    pub fn set(&mut self, index: NodeIndex, value: Value) {
+        *self.get_mut(index) = value;
+    }
+    
+    pub fn copy_to(&self, nodes: &mut [Value]) {
+        for (chunk, nodes) in self.chunks.iter().zip(nodes.chunks_mut(NODES_PER_CHUNK)) {
+            nodes.copy_from_slice(&chunk[..nodes.len()]);
+        }
+    }
+    
+    /// Pointers to the chunks, clones that share a chunk return the same pointer.
+    pub fn get_chunk_ptrs(&self) -> impl Iterator<Item = *const Chunk> + '_ {
+        self.chunks.iter().map(Arc::as_ptr)
+    }
+    
+    pub fn to_grid(&self) -> Grid {
+        debug_assert_eq!(self.num_nodes, NODES_PER_GRID);
+        
+        let mut grid = Grid::new(Value::default());
+        self.copy_to(&mut grid.nodes);
+        grid
+    }
+}
+
+impl From<&Grid> for ChunkedGrid {
+    fn from(grid: &Grid) -> Self {
+        ChunkedGrid::from_nodes(&grid.nodes)
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use octa_force::glam::IVec2;
+use crate::grid_manager::{BranchConflict, GridManager, SolveStatus};
+use crate::value::Value;
+use crate::budget::SolveBudget;
+
+/// Ticks a repair may take while diagnosing before it counts as solvable.
+const DIAGNOSIS_MAX_TICKS: usize = 10000;
+const DIAGNOSIS_MAX_NODES: usize = 16;
+
+/// Why a repair became unsolvable.
+#[derive(Clone, Debug)]
+pub struct Diagnosis {
+    /// Edits that can not be solved together. Without any one of them the rest can be solved.
+    pub edits: Vec<(IVec2, Value)>,
+    /// Locked nodes the edits can not be solved with. Unlocking any one of them while keeping the others
+    /// makes the edits solvable, locks the failure did not depend on are left out.
+    pub locked_nodes: Vec<IVec2>,
+    /// The nodes that killed the most branches of the minimal repair, with the number of branches.
+    /// Only a ranking of where branches died, not a minimal set of nodes causing the failure.
+    pub top_conflict_nodes: Vec<(IVec2, usize)>,
+    pub last_conflict: Option<BranchConflict>,
+}
+
+impl GridManager {
+    /// Reduces the edits of the current repair to a minimal unsolvable set by removing one edit at a time,
+    /// then does the same for the locked nodes branches died on, and reports the nodes the branches of that set died on.
+    pub fn diagnose(&self) -> Diagnosis {
+        let mut edits = self.edits.to_owned();
+        let mut i = 0;
+        while edits.len() > 1 && i < edits.len() {
+            let mut without = edits.to_owned();
+            without.remove(i);
+            
+            if self.solve_edits(&without, &[]).status == SolveStatus::Unsolvable {
+                edits = without;
+            } else {
+                i += 1;
+            }
+        }
+        
+        // Only locks a branch died on can cause the failure, the others never disagreed with a req.
+        let mut locked_nodes: Vec<_> = self.solve_edits(&edits, &[]).stats.conflicts_per_node.keys()
+            .copied()
+            .filter(|pos| self.is_locked(*pos))
+            .collect();
+        locked_nodes.sort_by_key(|pos| pos.to_array());
+        
+        let mut unlocked = vec![];
+        let mut i = 0;
+        while i < locked_nodes.len() {
+            unlocked.push(locked_nodes[i]);
+            
+            if self.solve_edits(&edits, &unlocked).status == SolveStatus::Unsolvable {
+                locked_nodes.remove(i);
+            } else {
+                unlocked.pop();
+                i += 1;
+            }
+        }
+        
+        let failed = self.solve_edits(&edits, &unlocked);
+        let mut conflict_nodes: Vec<_> = failed.stats.conflicts_per_node.iter()
+            .map(|(pos, count)| (*pos, *count))
+            .collect();
+        conflict_nodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.to_array().cmp(&b.0.to_array())));
+        conflict_nodes.truncate(DIAGNOSIS_MAX_NODES);
+        
+        Diagnosis {
+            edits,
+            locked_nodes,
+            top_conflict_nodes: conflict_nodes,
+            last_conflict: failed.stats.last_conflict,
+        }
+    }
+    
+    /// Solves the edits without pruning, so a failure is not caused by the beam, the queue size or the budget.
+    fn solve_edits(&self, edits: &[(IVec2, Value)], unlocked: &[IVec2]) -> GridManager {
+        let mut grid_manager = self.to_owned();
+        for pos in unlocked.iter() {
+            grid_manager.unlock(*pos);
+        }
+        grid_manager.incremental = false;
+        grid_manager.diagnose_failures = false;
+        grid_manager.budget = SolveBudget::default();
+        grid_manager.beam_width = None;
+        grid_manager.max_queue_size = None;
+        grid_manager.max_solutions = 1;
+        grid_manager.select_values(edits).expect("Edits of a repair have a rule");
+        
+        for _ in 0..DIAGNOSIS_MAX_TICKS {
+            if grid_manager.status != SolveStatus::Running {
+                break;
+            }
+            grid_manager.tick();
+        }
+        
+        grid_manager
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Unsolvable edits:")?;
+        for (pos, value) in self.edits.iter() {
+            writeln!(f, "    {pos} = {}", value.get_value_nr())?;
+        }
+        
+        if !self.locked_nodes.is_empty() {
+            writeln!(f, "Conflicting locked nodes:")?;
+            for pos in self.locked_nodes.iter() {
+                writeln!(f, "    {pos}")?;
+            }
+        }
+        
+        writeln!(f, "Nodes that killed the most branches:")?;
+        for (pos, count) in self.top_conflict_nodes.iter() {
+            writeln!(f, "    {pos}: {count}")?;
+        }
+        
+        if let Some(c) = self.last_conflict {
+            // Empty locked nodes conflict with every req.
+            let conflict_value = if c.conflict_value.is_none() {
+                "none".to_owned()
+            } else {
+                c.conflict_value.get_value_nr().to_string()
+            };
+            writeln!(f, "Last conflict: req {} of {} needs {} at {}, which is already {conflict_value}", 
+                     c.req_index, c.pos, c.required_value.get_value_nr(), c.conflict_pos)?;
+        }
+        
+        Ok(())
+    }
+}
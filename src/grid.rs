@@ -1,9 +1,14 @@
 use octa_force::glam::{ivec2, IVec2};
 use crate::value::Value;
-use crate::visualization::GRID_SIZE;
 
 pub type NodeIndex = usize;
 
+/// Side length of the grid. The `grid-128` feature raises it to measure larger repairs.
+#[cfg(not(feature = "grid-128"))]
+pub const GRID_SIZE: usize = 32;
+#[cfg(feature = "grid-128")]
+pub const GRID_SIZE: usize = 128;
+
 pub const NODES_PER_GRID: usize = GRID_SIZE * GRID_SIZE;
 
 #[derive(Copy, Clone)]
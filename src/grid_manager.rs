@@ -1,30 +1,173 @@
 use crate::value::VALUE_NONE;
-use std::collections::VecDeque;
-use octa_force::glam::IVec2;
-use octa_force::log::debug;
-use crate::grid::{get_node_index_from_pos, is_pos_in_grid, Grid, NodeIndex};
+use std::collections::{HashMap, HashSet, VecDeque};
+use octa_force::glam::{ivec2, IVec2};
+use octa_force::log::{debug, warn};
+use octa_force::anyhow::bail;
+use octa_force::OctaResult;
+use crate::chunked_grid::ChunkedGrid;
+use crate::order_queue::OrderQueue;
+use crate::grid::{get_node_index_from_pos, is_pos_in_grid, Grid, NodeIndex, NODES_PER_GRID};
+use crate::diagnosis::Diagnosis;
 use crate::rules::{Rule, RuleReq};
+use crate::score::ScoreStrategy;
 use crate::util::state_saver::State;
 use crate::value::{Value};
+use fastrand::Rng;
+use std::sync::Arc;
+use std::time::Instant;
+use crate::budget::{BudgetLimit, SolveBudget};
 
 #[derive(Clone)]
 pub struct GridManager {
     pub grid: Grid,
 
     pub working_grids: VecDeque<WorkingGrid>,
+    /// Distinct solutions of the current repair, best first.
     pub done_grids: Vec<Grid>,
+    done_grid_ranks: Vec<i64>,
+    /// The search goes on until this many distinct solutions are found or no working grid is left.
+    pub max_solutions: usize,
+    pub solution_ranking: SolutionRanking,
 
-    pub rules: Vec<Rule>,
+    pub rules: Arc<Vec<Rule>>,
     
+    /// Only changed through `set_score_strategy`, so the queue stays sorted by it.
+    score_strategy: ScoreStrategy,
+    pub candidate_order: CandidateOrder,
+    /// Breaks ties between working grids with the same score randomly instead of by insertion order.
+    pub random_tie_break: bool,
+    
+    /// Only the best this many children of an expanded working grid are queued.
+    pub(crate) beam_width: Option<usize>,
+    /// The worst working grids are dropped when the queue grows beyond this.
+    pub(crate) max_queue_size: Option<usize>,
+    /// Number of the best working grids expanded in parallel per tick. 1 expands a single grid on this thread.
+    pub parallel_expansions: usize,
+    /// Threads the parallel expansions are spread over, all cores if not set. The result does not depend on it.
+    pub num_threads: Option<usize>,
+    
+    pub stats: SolverStats,
+    pub status: SolveStatus,
+    
+    pub budget: SolveBudget,
+    /// Best queued working grid when the last repair ran out of budget.
+    pub best_partial: Option<Grid>,
+    start: Instant,
+    
+    /// New edits are merged into a running repair instead of restarting it.
+    pub incremental: bool,
+    /// Edits of the current repair.
+    pub edits: Vec<(IVec2, Value)>,
+    
+    /// Run `diagnose` when a repair becomes unsolvable. This re-solves the edits many times inside the failing tick.
+    pub diagnose_failures: bool,
+    pub diagnosis: Option<Diagnosis>,
+    
+    /// Nodes the solver may never change. Indexed by node index.
+    locked: Arc<Vec<bool>>,
+    
+    /// The rng is reset to this seed on every edit, so a repair is reproducible from the seed and the grid.
+    seed: u64,
+    pub(crate) rng: Rng,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum SolutionRanking {
+    /// Score of the working grid given by the score strategy.
+    #[default]
+    Score,
+    /// Fewest nodes that were set to a different value.
+    ChangedNodes,
+}
+
+/// Order in which the reqs of a value are tried when an order is ticked.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum CandidateOrder {
+    /// Order in which the reqs were learned.
+    #[default]
+    Learned,
+    Shuffled,
+    /// Shuffled, reqs with a higher weight tend to come first.
+    Weighted,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct SolverStats {
+    pub ticks: usize,
+    pub peak_queue_size: usize,
+    pub branches_created: usize,
+    /// Branches that died because a req conflicted with an already set node.
+    pub branches_died: usize,
+    /// Full restarts after a contradiction, only used by the classic WFC.
+    pub restarts: usize,
+    pub pruned_by_beam: usize,
+    pub pruned_by_queue: usize,
+    /// Branches that died because a req needed a different value on a locked node.
+    pub lock_conflicts: usize,
+    /// Working grids and solutions dropped because they changed a node that was locked while the repair ran.
+    pub dropped_by_locks: usize,
+    /// How often a branch died because a req needed a different value on this already set node.
+    pub conflicts_per_node: HashMap<IVec2, usize>,
+    pub last_conflict: Option<BranchConflict>,
+}
+
+impl SolverStats {
+    /// Adds the counters of an expansion.
+    pub fn add(&mut self, other: SolverStats) {
+        self.ticks += other.ticks;
+        self.peak_queue_size = self.peak_queue_size.max(other.peak_queue_size);
+        self.branches_created += other.branches_created;
+        self.branches_died += other.branches_died;
+        self.restarts += other.restarts;
+        self.pruned_by_beam += other.pruned_by_beam;
+        self.pruned_by_queue += other.pruned_by_queue;
+        self.lock_conflicts += other.lock_conflicts;
+        self.dropped_by_locks += other.dropped_by_locks;
+        for (pos, count) in other.conflicts_per_node {
+            *self.conflicts_per_node.entry(pos).or_default() += count;
+        }
+        if other.last_conflict.is_some() {
+            self.last_conflict = other.last_conflict;
+        }
+    }
+    
+    pub fn get_num_pruned(&self) -> usize {
+        self.pruned_by_beam + self.pruned_by_queue
+    }
+}
+
+/// A req of the value at `pos` needed `required_value` at `conflict_pos`, which was already set to `conflict_value`.
+#[derive(Clone, Copy, Debug)]
+pub struct BranchConflict {
+    pub pos: IVec2,
+    pub req_index: usize,
+    pub conflict_pos: IVec2,
+    pub conflict_value: Value,
+    pub required_value: Value,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum SolveStatus {
+    #[default]
+    Idle,
+    Running,
+    Solved,
+    /// All working grids died without a solution.
+    Unsolvable,
+    /// No working grid is left, but some were pruned by the beam or the queue size, so a solution may still exist.
+    Exhausted,
+    BudgetExceeded(BudgetLimit),
 }
 
 #[derive(Clone)]
 pub struct WorkingGrid {
-    pub full_grid: Grid,
-    pub empty_grid: Grid,
-    pub orders: VecDeque<(IVec2, bool)>,
+    pub full_grid: ChunkedGrid,
+    pub empty_grid: ChunkedGrid,
+    pub orders: OrderQueue,
     pub satisfied_count: usize,
     pub set_count: usize,
+    pub score: i64,
+    pub tie_break: u32,
 }
 
 impl GridManager {
@@ -33,55 +176,429 @@ impl GridManager {
             grid,
             working_grids: VecDeque::new(),
             done_grids: Vec::new(),
-            rules,
+            done_grid_ranks: Vec::new(),
+            max_solutions: 1,
+            solution_ranking: SolutionRanking::default(),
+            rules: Arc::new(rules),
+            score_strategy: ScoreStrategy::default(),
+            candidate_order: CandidateOrder::default(),
+            random_tie_break: false,
+            beam_width: None,
+            max_queue_size: None,
+            parallel_expansions: 1,
+            num_threads: None,
+            stats: SolverStats::default(),
+            status: SolveStatus::default(),
+            budget: SolveBudget::default(),
+            best_partial: None,
+            start: Instant::now(),
+            incremental: false,
+            edits: vec![],
+            diagnose_failures: false,
+            diagnosis: None,
+            locked: Arc::new(vec![false; NODES_PER_GRID]),
+            seed: 0,
+            rng: Rng::new(),
+        }.with_seed(fastrand::u64(..))
+    }
+    
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.set_seed(seed);
+        self
+    }
+    
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Rng::with_seed(seed);
+    }
+    
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_score_strategy(&self) -> ScoreStrategy {
+        self.score_strategy
+    }
+    
+    /// Rescores and sorts the queued working grids, `insert_working_grid` needs the queue sorted by the current strategy.
+    pub fn set_score_strategy(&mut self, score_strategy: ScoreStrategy) {
+        self.score_strategy = score_strategy;
+        for working_grid in self.working_grids.iter_mut() {
+            working_grid.score = score_strategy.get_score(working_grid);
         }
+        self.working_grids.make_contiguous()
+            .sort_by(|a, b| a.score.cmp(&b.score).then(a.tie_break.cmp(&b.tie_break)));
     }
 
-    pub fn select_value(&mut self, pos: IVec2, value: Value) {
-        self.working_grids.clear();
+    pub fn get_beam_width(&self) -> Option<usize> {
+        self.beam_width
+    }
+    
+    pub fn set_beam_width(&mut self, beam_width: Option<usize>) -> OctaResult<()> {
+        if beam_width == Some(0) {
+            bail!("Beam width must be at least 1");
+        }
         
-        let node_index = get_node_index_from_pos(pos);
+        self.beam_width = beam_width;
+        Ok(())
+    }
+    
+    pub fn get_max_queue_size(&self) -> Option<usize> {
+        self.max_queue_size
+    }
+    
+    pub fn set_max_queue_size(&mut self, max_queue_size: Option<usize>) -> OctaResult<()> {
+        if max_queue_size == Some(0) {
+            bail!("Max queue size must be at least 1");
+        }
+        
+        self.max_queue_size = max_queue_size;
+        Ok(())
+    }
+
+    pub fn is_locked(&self, pos: IVec2) -> bool {
+        is_pos_in_grid(pos) && self.locked[get_node_index_from_pos(pos)]
+    }
+    
+    pub fn lock(&mut self, pos: IVec2) {
+        self.set_locked(pos, true);
+        self.drop_grids_against_locks();
+    }
+    
+    pub fn unlock(&mut self, pos: IVec2) {
+        self.set_locked(pos, false);
+    }
+    
+    /// Locks all nodes from `min` to `max` inclusive.
+    pub fn lock_region(&mut self, min: IVec2, max: IVec2) {
+        self.set_region_locked(min, max, true);
+        self.drop_grids_against_locks();
+    }
+    
+    pub fn unlock_region(&mut self, min: IVec2, max: IVec2) {
+        self.set_region_locked(min, max, false);
+    }
+    
+    pub fn unlock_all(&mut self) {
+        Arc::make_mut(&mut self.locked).fill(false);
+    }
+    
+    pub fn get_locked(&self) -> &[bool] {
+        &self.locked
+    }
+    
+    pub fn get_num_locked(&self) -> usize {
+        self.locked.iter().filter(|l| **l).count()
+    }
+    
+    fn set_locked(&mut self, pos: IVec2, locked: bool) {
+        if is_pos_in_grid(pos) {
+            Arc::make_mut(&mut self.locked)[get_node_index_from_pos(pos)] = locked;
+        }
+    }
+    
+    fn set_region_locked(&mut self, min: IVec2, max: IVec2, locked: bool) {
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                self.set_locked(ivec2(x, y), locked);
+            }
+        }
+    }
+    
+    /// A running repair drops the working grids and solutions that already changed a node that is locked now.
+    /// The expansions read the locks of the manager, so unlocking needs nothing else.
+    fn drop_grids_against_locks(&mut self) {
+        if self.status != SolveStatus::Running {
+            return;
+        }
+        
+        let locked: Vec<_> = self.locked.iter().enumerate()
+            .filter(|(_, l)| **l)
+            .map(|(node_index, _)| node_index)
+            .collect();
+        let grid = self.grid;
+        let num_working_grids = self.working_grids.len();
+        self.working_grids.retain(|w| {
+            locked.iter().all(|i| w.full_grid.get(*i).color_index == grid.nodes[*i].color_index)
+        });
+        self.stats.dropped_by_locks += num_working_grids - self.working_grids.len();
+        
+        let done: Vec<_> = self.done_grids.drain(..).zip(self.done_grid_ranks.drain(..)).collect();
+        for (done_grid, rank) in done {
+            if locked.iter().all(|i| done_grid.nodes[*i].color_index == grid.nodes[*i].color_index) {
+                self.done_grids.push(done_grid);
+                self.done_grid_ranks.push(rank);
+            } else {
+                self.stats.dropped_by_locks += 1;
+            }
+        }
+    }
+    
+    /// Heuristic: the last repair failed and at least one branch died on a locked node or was dropped by a new lock.
+    /// This does not prove that the repair would succeed without the locks, other conflicts may kill the same branches.
+    pub fn is_blocked_by_locks(&self) -> bool {
+        self.status == SolveStatus::Unsolvable && (self.stats.lock_conflicts > 0 || self.stats.dropped_by_locks > 0)
+    }
+
+    pub fn select_value(&mut self, pos: IVec2, value: Value) -> OctaResult<()> {
+        self.select_values(&[(pos, value)])
+    }
+    
+    /// Places all values at once and repairs the combined edit. Later placements on the same node win.
+    /// In incremental mode a running repair keeps its working grids and the placements are added to them.
+    /// Fails if a value has no rule.
+    pub fn select_values(&mut self, placements: &[(IVec2, Value)]) -> OctaResult<()> {
+        for (pos, value) in placements.iter() {
+            if value.is_none() {
+                bail!("Can not place an empty value at {pos}");
+            }
+            if value.get_value_nr() as usize >= self.rules.len() {
+                bail!("Value {} at {pos} has no rule, there are only {} values", value.get_value_nr(), self.rules.len());
+            }
+        }
+        
+        let edits = self.filter_edits(placements);
+        if edits.is_empty() {
+            return Ok(());
+        }
+        
+        let merge = self.incremental && self.status == SolveStatus::Running;
+        if merge && self.merge_edits(&edits) {
+            self.edits.extend(edits);
+            return Ok(());
+        }
+        
+        let edits = if merge {
+            // No working grid could take the new edits, so restart with all edits of this repair.
+            let mut all_edits = self.edits.to_owned();
+            all_edits.extend(edits);
+            self.filter_edits(&all_edits)
+        } else {
+            edits
+        };
+        
+        self.working_grids.clear();
+        self.done_grids.clear();
+        self.done_grid_ranks.clear();
+        self.stats = SolverStats::default();
+        self.status = SolveStatus::Running;
+        self.diagnosis = None;
+        self.best_partial = None;
+        self.start = Instant::now();
+        self.rng = Rng::with_seed(self.seed);
         
         let mut working_grid: WorkingGrid = self.grid.to_owned().into();
-        working_grid.set_node_value_with_node_index(node_index, value, false);
-        working_grid.orders.push_back((pos, false));
-        working_grid.empty_grid.nodes[node_index].set_order(true);
+        for (pos, value) in edits.iter() {
+            working_grid.add_edit(*pos, *value);
+        }
         
         self.working_grids.push_back(working_grid);
+        self.edits = edits;
+        Ok(())
+    }
+    
+    /// Drops placements outside of the grid, on locked nodes and all but the last one per node.
+    fn filter_edits(&self, placements: &[(IVec2, Value)]) -> Vec<(IVec2, Value)> {
+        let mut placed = HashSet::new();
+        let mut edits = vec![];
+        for (pos, value) in placements.iter().rev() {
+            if !is_pos_in_grid(*pos) || !placed.insert(*pos) {
+                continue;
+            }
+            
+            if self.is_locked(*pos) {
+                warn!("Node {pos} is locked");
+                continue;
+            }
+            
+            edits.push((*pos, *value));
+        }
+        
+        edits.reverse();
+        edits
+    }
+    
+    /// Adds the edits to every working grid. Grids that already set one of the nodes to another value are dropped.
+    /// Solutions found so far do not contain the edits, so they are dropped as well.
+    fn merge_edits(&mut self, edits: &[(IVec2, Value)]) -> bool {
+        self.done_grids.clear();
+        self.done_grid_ranks.clear();
+        
+        let working_grids: Vec<_> = self.working_grids.drain(..).collect();
+        for mut working_grid in working_grids {
+            if edits.iter().all(|(pos, value)| working_grid.add_edit(*pos, *value)) {
+                self.insert_working_grid(working_grid);
+            }
+        }
+        
+        !self.working_grids.is_empty()
     }
     
     pub fn tick(&mut self) -> bool {
+        if self.status == SolveStatus::Running {
+            if let Some(limit) = self.get_exceeded_limit(self.start) {
+                self.exceed_budget(limit);
+                return false
+            }
+        }
         
-        let working_grid = self.working_grids.pop_front();
-        if working_grid.is_none() {
+        if self.working_grids.is_empty() {
+            if self.status == SolveStatus::Running {
+                self.update_status();
+            }
             return false
         }
         
-        let mut working_grid = working_grid.unwrap();
-        let order = working_grid.orders.pop_front();
-        if order.is_none() {
-            return true
+        let done_working_grids = if self.parallel_expansions > 1 {
+            self.tick_parallel()
+        } else {
+            let mut working_grid = self.working_grids.pop_front().unwrap();
+            self.stats.ticks += 1;
+            match working_grid.orders.pop_front() {
+                Some((pos, satisfied)) => self.tick_order_on_working_grid(working_grid, pos, satisfied),
+                None => vec![working_grid],
+            }
+        };
+
+        for done_working_grid in done_working_grids {
+            self.insert_done_grid(&done_working_grid);
         }
+        self.update_status();
         
-        let (pos, satisfied) = order.unwrap();
-        let done_working_grids = self.tick_order_on_working_grid(working_grid, pos, satisfied);
-
-        if !done_working_grids.is_empty() {
-            self.grid = done_working_grids[0].full_grid;
+        true
+    }
+    
+    /// Ends the repair if enough solutions were found or no working grid is left.
+    fn update_status(&mut self) {
+        if self.done_grids.len() >= self.max_solutions.max(1)
+            || (!self.done_grids.is_empty() && self.working_grids.is_empty()) {
+            self.grid = self.done_grids[0];
             self.working_grids.clear();
+            self.status = SolveStatus::Solved;
+        } else if self.working_grids.is_empty() && self.stats.get_num_pruned() > 0 {
+            self.status = SolveStatus::Exhausted;
+        } else if self.working_grids.is_empty() {
+            self.status = SolveStatus::Unsolvable;
+            
+            if self.diagnose_failures {
+                self.diagnosis = Some(self.diagnose());
+            }
         }
+    }
+    
+    pub fn tick_order_on_working_grid(&mut self, working_grid: WorkingGrid, pos: IVec2, satisfied: bool) -> Vec<WorkingGrid> {
+        let mut rng = self.rng.clone();
+        let mut stats = SolverStats::default();
+        let new_grids = self.expand_working_grid(working_grid, pos, satisfied, &mut rng, &mut stats);
         
-        true
+        self.rng = rng;
+        self.stats.add(stats);
+        self.queue_new_grids(new_grids)
+    }
+    
+    /// Tries every req of the value at `pos` and returns the working grids that survived.
+    pub fn expand_working_grid(&self, working_grid: WorkingGrid, pos: IVec2, satisfied: bool, rng: &mut Rng, stats: &mut SolverStats) -> Vec<WorkingGrid> {
+        self.get_expander().expand_working_grid(working_grid, pos, satisfied, rng, stats)
     }
     
-    pub fn tick_order_on_working_grid(&mut self, mut working_grid: WorkingGrid, pos: IVec2, satisfied: bool) -> Vec<WorkingGrid> {
+    pub(crate) fn get_expander(&self) -> Expander {
+        Expander {
+            rules: self.rules.to_owned(),
+            locked: self.locked.to_owned(),
+            candidate_order: self.candidate_order,
+        }
+    }
+    
+    /// Queues the open working grids and returns the done ones.
+    pub(crate) fn queue_new_grids(&mut self, new_grids: Vec<WorkingGrid>) -> Vec<WorkingGrid> {
+        let (done_grids, mut open_grids): (Vec<_>, Vec<_>) = new_grids.into_iter()
+            .partition(|w| w.orders.is_empty());
+        
+        if let Some(beam_width) = self.beam_width {
+            if open_grids.len() > beam_width {
+                open_grids.sort_by_cached_key(|w| self.score_strategy.get_score(w));
+                self.stats.pruned_by_beam += open_grids.len() - beam_width;
+                open_grids.truncate(beam_width);
+            }
+        }
+        
+        for new_working_grid in open_grids {
+            self.insert_working_grid(new_working_grid);
+        }
+
+        done_grids
+    }
+    
+    fn insert_done_grid(&mut self, working_grid: &WorkingGrid) {
+        let grid = working_grid.full_grid.to_grid();
+        if self.done_grids.iter().any(|g| g.nodes == grid.nodes) {
+            return;
+        }
+        
+        let rank = match self.solution_ranking {
+            SolutionRanking::Score => self.score_strategy.get_score(working_grid),
+            SolutionRanking::ChangedNodes => working_grid.get_changed_count() as i64,
+        };
+        
+        let index = self.done_grid_ranks.partition_point(|r| *r <= rank);
+        self.done_grids.insert(index, grid);
+        self.done_grid_ranks.insert(index, rank);
+    }
+    
+    /// Replaces the grid with another solution of the last repair.
+    pub fn select_solution(&mut self, index: usize) {
+        if let Some(grid) = self.done_grids.get(index) {
+            self.grid = *grid;
+        }
+    }
+    
+    pub fn get_reqs_for_value(&self, value_type: Value) -> &[RuleReq] {
+        self.rules[value_type.get_value_nr() as usize].get_reqs()
+    } 
+    
+    pub fn insert_working_grid(&mut self, mut working_grid: WorkingGrid) {
+        working_grid.score = self.score_strategy.get_score(&working_grid);
+        working_grid.tie_break = if self.random_tie_break { self.rng.u32(..) } else { 0 };
+        
+        let res = self.working_grids.binary_search_by(|w| {
+            w.score.cmp(&working_grid.score).then(w.tie_break.cmp(&working_grid.tie_break))
+        });
+        let index = if res.is_err() { res.err().unwrap() } else { res.unwrap() };
+
+        self.working_grids.insert(index, working_grid);
+        
+        if let Some(max_queue_size) = self.max_queue_size {
+            self.stats.pruned_by_queue += self.working_grids.len().saturating_sub(max_queue_size);
+            self.working_grids.truncate(max_queue_size);
+        }
+        
+        self.stats.peak_queue_size = self.stats.peak_queue_size.max(self.working_grids.len());
+    }
+}
+
+/// The parts of the manager an expansion reads. Cloning only shares the rules and locks,
+/// so expansions can be sent to other threads.
+#[derive(Clone)]
+pub(crate) struct Expander {
+    rules: Arc<Vec<Rule>>,
+    locked: Arc<Vec<bool>>,
+    candidate_order: CandidateOrder,
+}
+
+impl Expander {
+    /// Tries every req of the value at `pos` and returns the working grids that survived.
+    pub fn expand_working_grid(&self, mut working_grid: WorkingGrid, pos: IVec2, satisfied: bool, rng: &mut Rng, stats: &mut SolverStats) -> Vec<WorkingGrid> {
         let node_index = get_node_index_from_pos(pos);
-        working_grid.empty_grid.nodes[node_index].set_order(false);
+        working_grid.empty_grid.get_mut(node_index).set_order(false);
         
         let value = working_grid.get_node_value_with_node_index(node_index);
+        if self.rules[value.get_value_nr() as usize].is_pairwise() {
+            return self.expand_pairwise(working_grid, pos, value, satisfied, rng, stats);
+        }
         
         let mut new_grids = vec![];
-        for rule_req in self.get_reqs_for_value(value) {
+        for req_index in self.get_candidate_order(value, rng) {
+            let rule_req = &self.rules[value.get_value_nr() as usize].get_reqs()[req_index];
 
             let mut grid_ok = true;
             let mut fully_satisfied = true;
@@ -95,24 +612,25 @@ impl GridManager {
                 }
 
                 let req_node_index = get_node_index_from_pos(req_pos);
-                let already_set_value = working_grid.empty_grid.nodes[req_node_index];
-
-                if already_set_value.is_none() {
-                    let req_satisfied = working_grid.full_grid.nodes[req_node_index].color_index == req_value.color_index;
-                    new_working_grid.set_node_value_with_node_index(req_node_index, *req_value, req_satisfied);
-                    
-                    new_working_grid.orders.push_back((req_pos, req_satisfied));
-                    new_working_grid.empty_grid.nodes[req_node_index].set_order(true);
+                let Some(already_set_value) = self.get_fixed_value(&working_grid, req_node_index) else {
+                    let req_satisfied = working_grid.full_grid.get(req_node_index).color_index == req_value.color_index;
+                    new_working_grid.set_node_with_order(req_pos, *req_value, req_satisfied);
                     
                     if !req_satisfied {
                         fully_satisfied = false;
                     }
-                } else {
-                    let already_set_value = already_set_value;
-                    
-                    if already_set_value.color_index != req_value.color_index {
-                        grid_ok = false;
-                    }
+                    continue
+                };
+                
+                if already_set_value.color_index != req_value.color_index {
+                    grid_ok = false;
+                    self.record_conflict(stats, BranchConflict {
+                        pos,
+                        req_index,
+                        conflict_pos: req_pos,
+                        conflict_value: already_set_value,
+                        required_value: *req_value,
+                    });
                 }
             }
 
@@ -124,49 +642,155 @@ impl GridManager {
             
             if grid_ok { 
                 new_grids.push(new_working_grid);
+                stats.branches_created += 1;
+            } else {
+                stats.branches_died += 1;
             }
         }
 
-        let mut done_grids = vec![];
-        for new_working_grid in new_grids {
-            if new_working_grid.orders.is_empty() {
-                done_grids.push(new_working_grid)
+        new_grids
+    }
+    
+    /// Expansion of a pairwise rule. Neighbors that already have an allowed value keep it,
+    /// the working grid branches over the allowed values of one other neighbor at a time.
+    fn expand_pairwise(&self, working_grid: WorkingGrid, pos: IVec2, value: Value, satisfied: bool, rng: &mut Rng, stats: &mut SolverStats) -> Vec<WorkingGrid> {
+        let reqs = self.rules[value.get_value_nr() as usize].get_reqs();
+        
+        // Allowed values of every offset, in candidate order.
+        let mut offsets: Vec<(IVec2, Vec<(usize, Value)>)> = vec![];
+        for req_index in self.get_candidate_order(value, rng) {
+            let (offset, req_value) = reqs[req_index].reqs[0];
+            match offsets.iter_mut().find(|(o, _)| *o == offset) {
+                Some((_, candidates)) => candidates.push((req_index, req_value)),
+                None => offsets.push((offset, vec![(req_index, req_value)])),
+            }
+        }
+        offsets.sort_by_key(|(offset, _)| offset.to_array());
+        
+        let mut kept = vec![];
+        let mut open = vec![];
+        for (offset, candidates) in offsets {
+            let req_pos = pos + offset;
+            if !is_pos_in_grid(req_pos) {
+                continue
+            }
+            
+            let req_node_index = get_node_index_from_pos(req_pos);
+            if let Some(already_set_value) = self.get_fixed_value(&working_grid, req_node_index) {
+                if !candidates.iter().any(|(_, v)| v.color_index == already_set_value.color_index) {
+                    let (req_index, required_value) = candidates[0];
+                    self.record_conflict(stats, BranchConflict {
+                        pos,
+                        req_index,
+                        conflict_pos: req_pos,
+                        conflict_value: already_set_value,
+                        required_value,
+                    });
+                    stats.branches_died += 1;
+                    return vec![];
+                }
+                continue
+            }
+            
+            let current = working_grid.full_grid.get(req_node_index);
+            if candidates.iter().any(|(_, v)| v.color_index == current.color_index) {
+                kept.push((req_pos, current));
             } else {
-                self.insert_working_grid(new_working_grid);
+                open.push((req_pos, candidates));
             }
         }
-
-        done_grids
+        
+        if open.is_empty() && satisfied {
+            return vec![working_grid];
+        }
+        
+        let mut base = working_grid;
+        for (req_pos, current) in kept {
+            base.set_node_with_order(req_pos, current, true);
+        }
+        
+        // Branching over all open neighbors at once would create the cartesian product,
+        // so only the first one branches and the node is checked again before the other orders.
+        let Some((req_pos, candidates)) = open.first() else {
+            stats.branches_created += 1;
+            return vec![base];
+        };
+        if open.len() > 1 {
+            base.orders.push_front((pos, satisfied));
+            base.empty_grid.get_mut(get_node_index_from_pos(pos)).set_order(true);
+        }
+        
+        let new_grids: Vec<_> = candidates.iter()
+            .map(|(_, v)| {
+                let mut new_grid = base.to_owned();
+                new_grid.set_node_with_order(*req_pos, *v, false);
+                new_grid
+            })
+            .collect();
+        
+        stats.branches_created += new_grids.len();
+        new_grids
     }
     
-    pub fn get_reqs_for_value(&self, value_type: Value) -> &[RuleReq] {
-        &self.rules[value_type.get_value_nr() as usize].reqs
-    } 
+    /// Value of a node no req can change: set by this repair or locked, even if the locked value is empty.
+    fn get_fixed_value(&self, working_grid: &WorkingGrid, node_index: NodeIndex) -> Option<Value> {
+        let set_value = working_grid.empty_grid.get(node_index);
+        if set_value.is_some() {
+            Some(set_value)
+        } else if self.locked[node_index] {
+            Some(working_grid.full_grid.get(node_index))
+        } else {
+            None
+        }
+    }
     
-    pub fn insert_working_grid(&mut self, working_grid: WorkingGrid) {
-        let res = self.working_grids.binary_search_by(|w| {w.get_score().cmp(&working_grid.get_score())});
-        let index = if res.is_err() { res.err().unwrap() } else { res.unwrap() };
-
-        self.working_grids.insert(index, working_grid);
+    fn record_conflict(&self, stats: &mut SolverStats, conflict: BranchConflict) {
+        if self.locked[get_node_index_from_pos(conflict.conflict_pos)] {
+            stats.lock_conflicts += 1;
+        }
+        
+        *stats.conflicts_per_node.entry(conflict.conflict_pos).or_default() += 1;
+        stats.last_conflict = Some(conflict);
+    }
+    
+    fn get_candidate_order(&self, value: Value, rng: &mut Rng) -> Vec<usize> {
+        let rule = &self.rules[value.get_value_nr() as usize];
+        let mut order: Vec<usize> = (0..rule.get_reqs().len()).collect();
+        
+        match self.candidate_order {
+            CandidateOrder::Learned => {}
+            CandidateOrder::Shuffled => rng.shuffle(&mut order),
+            CandidateOrder::Weighted => {
+                // Weighted sampling without replacement: sort by u^(1 / weight) with u uniform in (0, 1].
+                let keys: Vec<f32> = rule.get_weights().iter()
+                    .map(|w| (1.0 - rng.f32()).powf(1.0 / w.max(f32::EPSILON)))
+                    .collect();
+                order.sort_by(|a, b| keys[*b].total_cmp(&keys[*a]));
+            }
+        }
+        
+        order
     }
 }
 
 impl From<Grid> for WorkingGrid {
     fn from(grid: Grid) -> Self {
         WorkingGrid {
-            full_grid: grid,
-            orders: VecDeque::new(),
+            full_grid: ChunkedGrid::from(&grid),
+            orders: OrderQueue::new(),
             satisfied_count: 0,
-            empty_grid: Grid::new(VALUE_NONE),
+            empty_grid: ChunkedGrid::new(NODES_PER_GRID, VALUE_NONE),
             set_count: 0,
+            score: 0,
+            tie_break: 0,
         }
     }
 }
 
 impl WorkingGrid {
     pub fn set_node_value_with_node_index(&mut self, node_index: NodeIndex, value: Value, satisfied: bool) {
-        self.full_grid.nodes[node_index] = value;
-        self.empty_grid.nodes[node_index] = value;
+        self.full_grid.set(node_index, value);
+        self.empty_grid.set(node_index, value);
         self.set_count += 1;
         
         if satisfied {
@@ -175,11 +799,33 @@ impl WorkingGrid {
     }
 
     pub fn get_node_value_with_node_index(&mut self, node_index: NodeIndex) -> Value {
-        self.empty_grid.nodes[node_index]
+        self.empty_grid.get(node_index)
     }
 
-    pub fn get_score(&self) -> i64 {
-        (self.orders.len() * 10 + self.set_count) as i64
+    /// Sets the node and adds an order for it. The node counts as already set, so the repair can not change it again.
+    /// Returns false if the node is already set to another value.
+    pub fn add_edit(&mut self, pos: IVec2, value: Value) -> bool {
+        let node_index = get_node_index_from_pos(pos);
+        let set_value = self.empty_grid.get(node_index);
+        if set_value.is_some() {
+            return set_value.color_index == value.color_index;
+        }
+        
+        self.set_node_with_order(pos, value, false);
+        true
+    }
+    
+    /// Sets a node and adds an order to check the reqs of its new value.
+    pub fn set_node_with_order(&mut self, pos: IVec2, value: Value, satisfied: bool) {
+        let node_index = get_node_index_from_pos(pos);
+        self.set_node_value_with_node_index(node_index, value, satisfied);
+        self.orders.push_back((pos, satisfied));
+        self.empty_grid.get_mut(node_index).set_order(true);
+    }
+    
+    /// Number of nodes that were set to a value they did not have before.
+    pub fn get_changed_count(&self) -> usize {
+        self.set_count - self.satisfied_count
     }
 }
 
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use octa_force::glam::IVec2;
+
+/// Position of a node whose reqs still have to be checked and whether its value was already satisfied when set.
+pub type Order = (IVec2, bool);
+
+type List = Option<Arc<Node>>;
+
+struct Node {
+    order: Order,
+    next: List,
+}
+
+/// Persistent FIFO of orders. Clones share all entries, so branching a working grid does not copy its orders
+/// and a push only allocates the new entry.
+///
+/// The queue is a front list and a reversed back list. When the front runs empty the back is reversed into it,
+/// branches that share the back list each do this once.
+#[derive(Clone, Default)]
+pub struct OrderQueue {
+    front: List,
+    back: List,
+    len: usize,
+}
+
+impl OrderQueue {
+    pub fn new() -> Self {
+        OrderQueue::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_back(&mut self, order: Order) {
+        self.back = Some(Arc::new(Node { order, next: self.back.take() }));
+        self.len += 1;
+    }
+
+    pub fn push_front(&mut self, order: Order) {
+        self.front = Some(Arc::new(Node { order, next: self.front.take() }));
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<Order> {
+        if self.front.is_none() {
+            let mut back = self.back.take();
+            while let Some(node) = back {
+                self.front = Some(Arc::new(Node { order: node.order, next: self.front.take() }));
+                back = node.next.to_owned();
+            }
+        }
+
+        let node = self.front.take()?;
+        self.front = node.next.to_owned();
+        self.len -= 1;
+        Some(node.order)
+    }
+}
+
+impl Drop for Node {
+    /// Unlinks the entries that are not shared one by one, dropping a long list recursively could overflow the stack.
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(node) = next {
+            next = match Arc::try_unwrap(node) {
+                Ok(mut node) => node.next.take(),
+                Err(_) => break,
+            };
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use fastrand::Rng;
+use octa_force::glam::IVec2;
+use crate::grid_manager::{Expander, GridManager, SolverStats, WorkingGrid};
+
+type Expansion = (WorkingGrid, IVec2, bool, Rng);
+type ExpansionResult = Vec<(Vec<WorkingGrid>, SolverStats)>;
+type Job = (Expander, Vec<Expansion>, Sender<ExpansionResult>);
+
+/// `available_parallelism` reads cgroup files on linux, so it is only asked once.
+fn get_num_cores() -> usize {
+    static NUM_CORES: OnceLock<usize> = OnceLock::new();
+    *NUM_CORES.get_or_init(|| thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+/// Threads that live as long as the program, so a tick does not spawn any.
+/// Threads are only added when a tick asks for more than there are.
+struct WorkerPool {
+    senders: Mutex<Vec<Sender<Job>>>,
+}
+
+impl WorkerPool {
+    fn get() -> &'static WorkerPool {
+        static POOL: OnceLock<WorkerPool> = OnceLock::new();
+        POOL.get_or_init(|| WorkerPool {
+            senders: Mutex::new(vec![]),
+        })
+    }
+
+    fn get_senders(&self, num_workers: usize) -> Vec<Sender<Job>> {
+        let mut senders = self.senders.lock().unwrap();
+        while senders.len() < num_workers {
+            let (sender, receiver) = channel();
+            thread::Builder::new()
+                .name(format!("expansion {}", senders.len()))
+                .spawn(move || work(receiver))
+                .expect("Failed to spawn expansion thread");
+            senders.push(sender);
+        }
+
+        senders[..num_workers].to_vec()
+    }
+}
+
+fn work(receiver: Receiver<Job>) {
+    while let Ok((expander, chunk, result)) = receiver.recv() {
+        let _ = result.send(expand(&expander, chunk));
+    }
+}
+
+fn expand(expander: &Expander, chunk: Vec<Expansion>) -> ExpansionResult {
+    chunk.into_iter()
+        .map(|(working_grid, pos, satisfied, mut rng)| {
+            let mut stats = SolverStats::default();
+            let new_grids = expander.expand_working_grid(working_grid, pos, satisfied, &mut rng, &mut stats);
+            (new_grids, stats)
+        })
+        .collect()
+}
+
+impl GridManager {
+    /// Pops the best `parallel_expansions` working grids and expands them on `num_threads` threads.
+    /// Every expansion gets its own rng drawn from the rng of the manager and the results are queued in queue order,
+    /// so a repair only depends on the seed and not on the number of threads.
+    pub(crate) fn tick_parallel(&mut self) -> Vec<WorkingGrid> {
+        let mut expansions: Vec<Expansion> = vec![];
+        let mut done_grids = vec![];
+        while expansions.len() < self.parallel_expansions {
+            let Some(mut working_grid) = self.working_grids.pop_front() else {
+                break
+            };
+
+            self.stats.ticks += 1;
+            match working_grid.orders.pop_front() {
+                Some((pos, satisfied)) => expansions.push((working_grid, pos, satisfied, Rng::with_seed(self.rng.u64(..)))),
+                None => done_grids.push(working_grid),
+            }
+        }
+
+        let num_threads = self.num_threads.unwrap_or(get_num_cores()).clamp(1, expansions.len().max(1));
+        let chunk_size = expansions.len().div_ceil(num_threads).max(1);
+
+        let mut chunks = vec![];
+        let mut expansions = expansions.into_iter();
+        loop {
+            let chunk: Vec<_> = expansions.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                break
+            }
+            chunks.push(chunk);
+        }
+
+        let expander = self.get_expander();
+        let mut chunks = chunks.into_iter();
+        let first_chunk = chunks.next().unwrap_or_default();
+        // The first chunk is expanded on this thread, so a single core does not need any worker.
+        let workers = WorkerPool::get().get_senders(chunks.len());
+        let receivers: Vec<_> = chunks.zip(workers.iter())
+            .map(|(chunk, worker)| {
+                let (sender, receiver) = channel();
+                worker.send((expander.to_owned(), chunk, sender)).expect("Expansion thread stopped");
+                receiver
+            })
+            .collect();
+
+        let mut results = expand(&expander, first_chunk);
+        for receiver in receivers {
+            results.extend(receiver.recv().expect("Expansion thread panicked"));
+        }
+
+        for (new_grids, stats) in results {
+            self.stats.add(stats);
+            done_grids.extend(self.queue_new_grids(new_grids));
+        }
+
+        done_grids
+    }
+}
@@ -8,12 +8,21 @@ use crate::visualization::Visualization;
 
 mod util;
 mod render;
-mod grid;
-mod rules;
+pub mod grid;
+pub mod rules;
 mod visualization;
-mod grid_manager;
-mod value;
-mod rule_gen;
+pub mod grid_manager;
+pub mod value;
+pub mod chunked_grid;
+pub mod order_queue;
+pub mod rule_gen;
+pub mod score;
+pub mod diagnosis;
+pub mod budget;
+pub mod solve;
+mod parallel;
+pub mod solver;
+pub mod wfc;
 
 pub struct RenderState {
     visualization: Visualization,
@@ -16,6 +16,7 @@ pub fn grid_shader() -> &'static [u8] {
         #define BORDER_SIZE 0.02
         #define SELECTOR_COLOR rgb(100, 100, 100)
         #define ORDER_COLOR rgb(0, 0, 255)
+        #define LOCK_COLOR rgb(255, 0, 0)
     
         layout(local_size_x = 32, local_size_y = 32, local_size_z = 1) in;
     
@@ -46,6 +47,7 @@ pub fn grid_shader() -> &'static [u8] {
         #define POS_IN_BOUNDS(pos) pos.x < CHUNK_SIZE && pos.y < CHUNK_SIZE
         #define GET_NODE_AT(pos) chunk_buffer.data[pos.x * CHUNK_SIZE + pos.y] & 255
         #define IS_NODE_ORDER(pos) bool((chunk_buffer.data[pos.x * CHUNK_SIZE + pos.y] >> 8) & 1)
+        #define IS_NODE_LOCKED(pos) bool((chunk_buffer.data[pos.x * CHUNK_SIZE + pos.y] >> 9) & 1)
     
         vec4 node_color(uint data) {
             return vec4(NODE_COLOR(data));
@@ -63,6 +65,10 @@ pub fn grid_shader() -> &'static [u8] {
             if (IS_NODE_ORDER(node_pos) && at_boarder(in_node_pos, BORDER_SIZE * 2.0)) {
                 return ORDER_COLOR;
             }
+            
+            if (IS_NODE_LOCKED(node_pos) && at_boarder(in_node_pos, BORDER_SIZE * 3.0)) {
+                return LOCK_COLOR;
+            }
     
             return color;
         }
@@ -1,13 +1,20 @@
 use octa_force::glam::{ivec2, IVec2, Vec2};
-use crate::grid::{get_node_index_from_pos, Grid};
+use crate::grid::{get_node_index_from_pos, Grid, GRID_SIZE};
 use crate::value::Value;
-use crate::visualization::GRID_SIZE;
 
 const PIXELS_PER_NODE: f32 = 30.0;
 
 pub struct Selector {
     pub selected_pos: Option<IVec2>,
     pub value_type_to_place: Value,
+    pub brush: Brush,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Brush {
+    Place,
+    Lock,
+    Unlock,
 }
 
 impl Selector {
@@ -15,6 +22,7 @@ impl Selector {
         Selector {
             selected_pos: None,
             value_type_to_place: Value::default(),
+            brush: Brush::Place,
         }
     }
 
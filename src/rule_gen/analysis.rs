@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use octa_force::glam::ivec2;
+use octa_force::anyhow::bail;
+use octa_force::OctaResult;
+use crate::grid::{get_node_index_from_pos, Grid, GRID_SIZE};
+use crate::grid_manager::GridManager;
+use crate::rule_gen::neighborhood::Neighborhood;
+use crate::rule_gen::{gen_rules_from_images, RuleGenSample, RuleGenSettings};
+use crate::rules::{Rule, RuleReq};
+use crate::value::{Value, ValueNr};
+
+#[derive(Clone, Debug)]
+pub struct AnalysisSettings {
+    pub candidates: Vec<Neighborhood>,
+    /// Number of random edits solved on a test grid per candidate.
+    pub trials: usize,
+    pub max_ticks_per_trial: usize,
+    pub seed: u64,
+    pub max_ambiguity: f32,
+    pub min_success_rate: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct CandidateReport {
+    pub neighborhood: Neighborhood,
+    pub num_offsets: usize,
+    pub num_values: usize,
+    pub num_reqs: usize,
+    /// Share of distinct neighborhoods that were seen around more than one center value.
+    pub ambiguity: f32,
+    pub trials: usize,
+    pub successes: usize,
+    /// Why the candidate could not be analysed, the other fields are zero then.
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NeighborhoodReport {
+    pub candidates: Vec<CandidateReport>,
+    /// Index into `candidates`.
+    pub recommended: Option<usize>,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        AnalysisSettings {
+            candidates: vec![
+                Neighborhood::VonNeumann,
+                Neighborhood::Moore,
+                Neighborhood::Diamond(2),
+                Neighborhood::Square(2),
+            ],
+            trials: 20,
+            max_ticks_per_trial: 10000,
+            seed: 0,
+            max_ambiguity: 0.05,
+            min_success_rate: 0.5,
+        }
+    }
+}
+
+impl CandidateReport {
+    fn failed(neighborhood: &Neighborhood, error: String) -> Self {
+        CandidateReport {
+            neighborhood: neighborhood.to_owned(),
+            num_offsets: 0,
+            num_values: 0,
+            num_reqs: 0,
+            ambiguity: 0.0,
+            trials: 0,
+            successes: 0,
+            error: Some(error),
+        }
+    }
+    
+    pub fn get_success_rate(&self) -> f32 {
+        if self.trials == 0 {
+            return 0.0;
+        }
+        
+        self.successes as f32 / self.trials as f32
+    }
+}
+
+/// Learns rules for every candidate neighborhood and recommends the smallest one that is unambiguous and solvable.
+/// Border and palette options are taken from `settings`, its offsets are replaced by the candidates.
+/// A candidate that fails is reported with its error and never recommended.
+pub fn analyse_neighborhoods(samples: &[RuleGenSample], settings: &RuleGenSettings, analysis: &AnalysisSettings) -> NeighborhoodReport {
+    let candidates: Vec<_> = analysis.candidates.iter()
+        .map(|neighborhood| analyse_candidate(samples, settings, neighborhood, analysis)
+            .unwrap_or_else(|err| CandidateReport::failed(neighborhood, err.to_string())))
+        .collect();
+    
+    let recommended = candidates.iter()
+        .enumerate()
+        .filter(|(_, c)| c.error.is_none())
+        .filter(|(_, c)| c.ambiguity <= analysis.max_ambiguity && c.get_success_rate() >= analysis.min_success_rate)
+        .min_by_key(|(_, c)| c.num_offsets)
+        .map(|(i, _)| i);
+    
+    NeighborhoodReport {
+        candidates,
+        recommended,
+    }
+}
+
+fn analyse_candidate(samples: &[RuleGenSample], settings: &RuleGenSettings, neighborhood: &Neighborhood, analysis: &AnalysisSettings) -> OctaResult<CandidateReport> {
+    let mut candidate_settings = settings.to_owned();
+    candidate_settings.offsets = neighborhood.get_offsets();
+    candidate_settings.record_sources = false;
+    
+    let (rules, _) = gen_rules_from_images(samples, &candidate_settings)?;
+    
+    // The trial edit has to differ from the base value, otherwise a failed repair would look solved.
+    if rules.len() < 2 {
+        bail!("Sample has a single value, trial edits can not differ from it");
+    }
+    let successes = count_successful_trials(&rules, analysis)?;
+    
+    Ok(CandidateReport {
+        neighborhood: neighborhood.to_owned(),
+        num_offsets: candidate_settings.offsets.len(),
+        num_values: rules.len(),
+        num_reqs: rules.iter().map(|r| r.get_reqs().len()).sum(),
+        ambiguity: get_ambiguity(&rules),
+        trials: analysis.trials,
+        successes,
+        error: None,
+    })
+}
+
+fn get_ambiguity(rules: &[Rule]) -> f32 {
+    let mut centers: HashMap<&RuleReq, HashSet<Value>> = HashMap::new();
+    for rule in rules.iter() {
+        for rule_req in rule.get_reqs().iter() {
+            centers.entry(rule_req).or_default().insert(rule.value);
+        }
+    }
+    
+    if centers.is_empty() {
+        return 0.0;
+    }
+    
+    let ambiguous = centers.values().filter(|c| c.len() > 1).count();
+    ambiguous as f32 / centers.len() as f32
+}
+
+fn count_successful_trials(rules: &[Rule], analysis: &AnalysisSettings) -> OctaResult<usize> {
+    let mut rng = fastrand::Rng::with_seed(analysis.seed);
+    let mut successes = 0;
+    for _ in 0..analysis.trials {
+        let base_nr = rng.usize(0..rules.len());
+        let mut grid_manager = GridManager::new(Grid::new(Value::from_value_nr(base_nr as ValueNr)), rules.to_owned());
+        
+        let value_nr = (base_nr + 1 + rng.usize(0..rules.len() - 1)) % rules.len();
+        let value = Value::from_value_nr(value_nr as ValueNr);
+        let pos = ivec2(rng.i32(0..GRID_SIZE as i32), rng.i32(0..GRID_SIZE as i32));
+        grid_manager.select_value(pos, value)?;
+        
+        for _ in 0..analysis.max_ticks_per_trial {
+            if grid_manager.working_grids.is_empty() {
+                break;
+            }
+            grid_manager.tick();
+        }
+        
+        let solved = grid_manager.working_grids.is_empty() 
+            && grid_manager.grid.nodes[get_node_index_from_pos(pos)] == value;
+        if solved {
+            successes += 1;
+        }
+    }
+    
+    Ok(successes)
+}
+
+impl fmt::Display for NeighborhoodReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16} {:>8} {:>8} {:>8} {:>10} {:>8}", "neighborhood", "offsets", "values", "reqs", "ambiguity", "success")?;
+        for (i, c) in self.candidates.iter().enumerate() {
+            if let Some(error) = &c.error {
+                writeln!(f, "{:<16} failed: {error}", format!("{:?}", c.neighborhood))?;
+                continue;
+            }
+            
+            let marker = if self.recommended == Some(i) { " <-" } else { "" };
+            writeln!(f, "{:<16} {:>8} {:>8} {:>8} {:>10.3} {:>8.3}{marker}", 
+                     format!("{:?}", c.neighborhood), c.num_offsets, c.num_values, c.num_reqs, c.ambiguity, c.get_success_rate())?;
+        }
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::{fs, mem};
+use image::RgbaImage;
+use octa_force::anyhow::{anyhow, bail};
+use octa_force::OctaResult;
+use crate::grid::Grid;
+use crate::rule_gen::neighborhood::Neighborhood;
+use crate::rule_gen::palette::PaletteMode;
+use crate::rule_gen::{gen_rules_from_images, grid_to_image, HoleMode, RuleGenSample, RuleGenSettings, SampleBorder};
+use crate::rule_gen::overlapping::{OverlappingModel, OverlappingSettings};
+use crate::rule_gen::tiled::TiledModel;
+use crate::rules::Rule;
+use crate::value::ValueColor;
+
+/// Rule generation setup read from a `key = value` file. `#` starts a comment.
+///
+/// ```text
+/// sample = WaveFunctionCollapse/samples/Hogs.png 1.0
+/// neighborhood = diamond 2
+/// border = wrap
+/// palette = kmeans 8
+/// # or a fixed palette: palette = fixed 0 0 0, 255 255 255
+/// hole_color = 255 0 255
+/// holes = wildcard
+/// sources = true
+/// ```
+///
+/// `model = tiled WaveFunctionCollapse/tilesets/Knots.xml` loads the rules from a tileset instead of the samples.
+/// `model = overlapping 3` learns 3x3 patterns of the first sample with the configured palette.
+#[derive(Clone, Debug)]
+pub struct RuleGenConfig {
+    pub samples: Vec<RuleGenSample>,
+    pub neighborhood: Neighborhood,
+    pub settings: RuleGenSettings,
+    pub model: RuleModel,
+}
+
+/// Where the rules come from.
+#[derive(Clone, Debug, Default)]
+pub enum RuleModel {
+    /// Neighborhoods learned from the samples.
+    #[default]
+    Samples,
+    /// Pairwise tile adjacencies of a WaveFunctionCollapse tileset XML.
+    Tiled(String),
+    /// NxN patterns of the first sample.
+    Overlapping(i32),
+}
+
+/// What a solved grid of a model is drawn with, see `RuleGenConfig::gen_model`.
+pub enum ModelOutput {
+    /// One pixel per node in the color of its value. The value colors of the overlapping model are the
+    /// top left pixels of the patterns, so this also draws its output.
+    Colors(Vec<ValueColor>),
+    Tiled(TiledModel),
+}
+
+impl ModelOutput {
+    pub fn grid_to_image(&self, grid: &Grid) -> RgbaImage {
+        match self {
+            ModelOutput::Colors(value_colors) => grid_to_image(grid, value_colors),
+            ModelOutput::Tiled(model) => model.grid_to_image(grid),
+        }
+    }
+}
+
+impl RuleGenConfig {
+    pub fn load(path: &str) -> OctaResult<Self> {
+        let text = fs::read_to_string(path)?;
+        
+        let mut samples = vec![];
+        let mut neighborhood = Neighborhood::Moore;
+        let mut border = SampleBorder::default();
+        let mut palette = PaletteMode::default();
+        let mut transparent_holes = true;
+        let mut hole_color = None;
+        let mut hole_mode = HoleMode::default();
+        let mut record_sources = false;
+        let mut model = RuleModel::default();
+        
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| anyhow!("{path}:{}: expected key = value", i + 1))?;
+            let value = value.trim();
+            
+            match key.trim() {
+                "sample" => {
+                    let mut parts = value.split_whitespace();
+                    let mut sample = RuleGenSample::new(parts.next().unwrap_or_default());
+                    if let Some(weight) = parts.next() {
+                        sample = sample.with_weight(weight.parse()?);
+                    }
+                    samples.push(sample);
+                }
+                "neighborhood" => neighborhood = value.parse()?,
+                "border" => border = match value {
+                    "skip" => SampleBorder::Skip,
+                    "wrap" => SampleBorder::Wrap,
+                    "clamp" => SampleBorder::Clamp,
+                    _ => bail!("{path}:{}: unknown border {value}", i + 1),
+                },
+                "palette" => palette = parse_palette(value)?,
+                "transparent_holes" => transparent_holes = value.parse()?,
+                "hole_color" => hole_color = Some(parse_color(value)?),
+                "holes" => hole_mode = match value {
+                    "skip" => HoleMode::Skip,
+                    "wildcard" => HoleMode::Wildcard,
+                    _ => bail!("{path}:{}: unknown hole mode {value}", i + 1),
+                },
+                "sources" => record_sources = value.parse()?,
+                "model" => model = parse_model(value)?,
+                key => bail!("{path}:{}: unknown key {key}", i + 1),
+            }
+        }
+        
+        if samples.is_empty() && !matches!(model, RuleModel::Tiled(_)) {
+            bail!("{path} has no sample");
+        }
+        
+        let mut settings = RuleGenSettings::new(neighborhood.get_offsets())
+            .with_border(border)
+            .with_palette(palette)
+            .with_holes(hole_color, hole_mode);
+        settings.transparent_holes = transparent_holes;
+        settings.record_sources = record_sources;
+        
+        Ok(RuleGenConfig {
+            samples,
+            neighborhood,
+            settings,
+            model,
+        })
+    }
+    
+    /// Rules and value colors of the configured model.
+    pub fn gen_rules(&self) -> OctaResult<(Vec<Rule>, Vec<ValueColor>)> {
+        let (rules, value_colors, _) = self.gen_model()?;
+        Ok((rules, value_colors))
+    }
+    
+    /// Like `gen_rules`, but also returns what solved grids are turned back into images with.
+    pub fn gen_model(&self) -> OctaResult<(Vec<Rule>, Vec<ValueColor>, ModelOutput)> {
+        match &self.model {
+            RuleModel::Samples => {
+                let (rules, value_colors) = gen_rules_from_images(&self.samples, &self.settings)?;
+                Ok((rules, value_colors.to_owned(), ModelOutput::Colors(value_colors)))
+            }
+            RuleModel::Tiled(path) => {
+                let mut model = TiledModel::from_xml(path)?;
+                let value_colors = model.get_value_colors();
+                let rules = mem::take(&mut model.rules);
+                Ok((rules, value_colors, ModelOutput::Tiled(model)))
+            }
+            RuleModel::Overlapping(n) => {
+                let mut settings = OverlappingSettings::new(*n);
+                settings.palette = self.settings.palette.to_owned();
+                let model = OverlappingModel::from_image(&self.samples[0].path, &settings)?;
+                let value_colors = model.get_value_colors();
+                Ok((model.rules, value_colors.to_owned(), ModelOutput::Colors(value_colors)))
+            }
+        }
+    }
+}
+
+fn parse_model(value: &str) -> OctaResult<RuleModel> {
+    let mut parts = value.split_whitespace();
+    let name = parts.next().unwrap_or_default();
+    let arg = parts.next();
+    
+    Ok(match name {
+        "samples" => RuleModel::Samples,
+        "tiled" => RuleModel::Tiled(arg.ok_or_else(|| anyhow!("Model tiled needs a tileset path"))?.to_owned()),
+        "overlapping" => RuleModel::Overlapping(arg.ok_or_else(|| anyhow!("Model overlapping needs a pattern size"))?.parse()?),
+        _ => bail!("Unknown model {value}"),
+    })
+}
+
+fn parse_palette(value: &str) -> OctaResult<PaletteMode> {
+    let mut parts = value.split_whitespace();
+    let name = parts.next().unwrap_or_default();
+    let args: Vec<_> = parts.collect();
+    let arg = || args.first().ok_or_else(|| anyhow!("Palette {name} needs an argument"));
+    
+    let size = || -> OctaResult<usize> {
+        let size = arg()?.parse()?;
+        if size == 0 {
+            bail!("Palette {name} needs a size of at least 1");
+        }
+        Ok(size)
+    };
+    
+    Ok(match name {
+        "exact" => PaletteMode::Exact,
+        "threshold" => PaletteMode::Threshold(arg()?.parse()?),
+        "kmeans" => PaletteMode::KMeans(size()?),
+        "median_cut" => PaletteMode::MedianCut(size()?),
+        "fixed" => {
+            let colors = value[name.len()..].split(',')
+                .map(parse_color)
+                .collect::<OctaResult<Vec<_>>>()?;
+            PaletteMode::Fixed(colors)
+        }
+        _ => bail!("Unknown palette {value}"),
+    })
+}
+
+fn parse_color(value: &str) -> OctaResult<ValueColor> {
+    let c: Vec<u8> = value.split_whitespace().map(|c| c.parse()).collect::<Result<_, _>>()?;
+    if c.len() != 3 {
+        bail!("Color {} needs r g b", value.trim());
+    }
+    
+    Ok(ValueColor::new(c[0], c[1], c[2]))
+}
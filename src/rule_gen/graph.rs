@@ -0,0 +1,93 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use crate::rules::Rule;
+use crate::value::{ValueColor, ValueNr};
+
+/// `(from, to, offset)`. Collapsed graphs have no offsets and every pair only once.
+pub type RuleEdge = (ValueNr, ValueNr, Option<(i32, i32)>);
+
+/// Adjacency relation implied by a rule set. Every `(value, offset, neighbor)` that appears in a req is an edge.
+pub struct RuleGraph {
+    pub nodes: Vec<(ValueNr, ValueColor)>,
+    pub edges: Vec<RuleEdge>,
+    pub collapsed: bool,
+}
+
+impl RuleGraph {
+    /// `value_colors` is the list given to the renderer, so it starts with the color of `VALUE_NONE`.
+    /// If `collapse` is set all offsets between two values become one undirected "can neighbor" edge.
+    pub fn new(rules: &[Rule], value_colors: &[ValueColor], collapse: bool) -> Self {
+        let nodes = rules.iter()
+            .map(|r| {
+                let value_nr = r.value.get_value_nr();
+                let color = value_colors.get(value_nr as usize + 1).copied().unwrap_or_default();
+                (value_nr, color)
+            })
+            .collect();
+        
+        let mut edges = BTreeSet::new();
+        for rule in rules.iter() {
+            let from = rule.value.get_value_nr();
+            for rule_req in rule.get_reqs().iter() {
+                for (offset, value) in rule_req.reqs.iter() {
+                    let to = value.get_value_nr();
+                    if collapse {
+                        edges.insert((from.min(to), from.max(to), None));
+                    } else {
+                        edges.insert((from, to, Some((offset.x, offset.y))));
+                    }
+                }
+            }
+        }
+        
+        RuleGraph {
+            nodes,
+            edges: edges.into_iter().collect(),
+            collapsed: collapse,
+        }
+    }
+    
+    pub fn to_dot(&self) -> String {
+        let (kind, connector) = if self.collapsed { ("graph", "--") } else { ("digraph", "->") };
+        
+        let mut dot = String::new();
+        writeln!(dot, "{kind} rules {{").unwrap();
+        writeln!(dot, "    node [style=filled];").unwrap();
+        for (value_nr, color) in self.nodes.iter() {
+            writeln!(dot, "    v{value_nr} [label=\"{value_nr}\", fillcolor=\"{}\"];", color.to_hex()).unwrap();
+        }
+        for (from, to, offset) in self.edges.iter() {
+            match offset {
+                Some((x, y)) => writeln!(dot, "    v{from} {connector} v{to} [label=\"{x},{y}\"];").unwrap(),
+                None => writeln!(dot, "    v{from} {connector} v{to};").unwrap(),
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        
+        dot
+    }
+    
+    pub fn to_graphml(&self) -> String {
+        let edge_default = if self.collapsed { "undirected" } else { "directed" };
+        
+        let mut xml = String::new();
+        writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(xml, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#).unwrap();
+        writeln!(xml, r#"  <key id="color" for="node" attr.name="color" attr.type="string"/>"#).unwrap();
+        writeln!(xml, r#"  <key id="offset" for="edge" attr.name="offset" attr.type="string"/>"#).unwrap();
+        writeln!(xml, r#"  <graph id="rules" edgedefault="{edge_default}">"#).unwrap();
+        for (value_nr, color) in self.nodes.iter() {
+            writeln!(xml, r#"    <node id="v{value_nr}"><data key="color">{}</data></node>"#, color.to_hex()).unwrap();
+        }
+        for (from, to, offset) in self.edges.iter() {
+            match offset {
+                Some((x, y)) => writeln!(xml, r#"    <edge source="v{from}" target="v{to}"><data key="offset">{x},{y}</data></edge>"#).unwrap(),
+                None => writeln!(xml, r#"    <edge source="v{from}" target="v{to}"/>"#).unwrap(),
+            }
+        }
+        writeln!(xml, "  </graph>").unwrap();
+        writeln!(xml, "</graphml>").unwrap();
+        
+        xml
+    }
+}
@@ -1,63 +1,237 @@
-use image::{GenericImageView, ImageReader};
+mod sample;
+pub mod palette;
+pub mod neighborhood;
+pub mod config;
+pub mod analysis;
+pub mod stats;
+pub mod graph;
+pub mod overlapping;
+pub mod tiled;
+
+use image::{ImageReader, Rgba, RgbaImage};
+use octa_force::anyhow::bail;
 use octa_force::glam::{ivec2, IVec2};
 use octa_force::log::info;
 use octa_force::OctaResult;
-use crate::rules::{Rule, RuleReq};
-use crate::value::{Value, ValueColor, ValueNr};
+use crate::rule_gen::palette::{build_palette, PaletteMode};
+use crate::rule_gen::sample::Sample;
+use crate::rule_gen::stats::RuleStats;
+use crate::grid::{get_node_index_from_pos, Grid, GRID_SIZE};
+use crate::rules::{Rule, RuleReq, RuleSource};
+use crate::value::{to_value_colors, Value, ValueColor, ValueNr, MAX_VALUES};
+
+#[derive(Clone, Debug)]
+pub struct RuleGenSettings {
+    pub offsets: Vec<IVec2>,
+    pub border: SampleBorder,
+    pub palette: PaletteMode,
+    
+    /// Fully transparent pixels are holes.
+    pub transparent_holes: bool,
+    /// Pixels of this color are holes.
+    pub hole_color: Option<ValueColor>,
+    pub hole_mode: HoleMode,
+    
+    /// Store the sample coordinates of every occurrence of a req in `Rule::sources`.
+    pub record_sources: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleGenSample {
+    pub path: String,
+    /// Weight added to a req for every occurrence in this sample.
+    pub weight: f32,
+}
+
+/// How neighborhoods that reach over the edge of the sample are handled.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum SampleBorder {
+    /// Only learn from positions whose whole neighborhood lies inside the sample.
+    #[default]
+    Skip,
+    /// Treat the sample as periodic.
+    Wrap,
+    /// Repeat the edge pixels.
+    Clamp,
+}
 
-pub fn gen_rules_from_image(path: &str, offsets: Vec<IVec2>) -> OctaResult<(Vec<Rule>, Vec<ValueColor>)> {
-    let img = ImageReader::open(path)?.decode()?;
+/// How neighborhoods that touch a hole of the sample are handled. Holes never become a `Value`.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum HoleMode {
+    /// Do not learn from neighborhoods that touch a hole.
+    #[default]
+    Skip,
+    /// Leave the slots of holes out of the req, so they accept any value.
+    Wildcard,
+}
+
+impl RuleGenSettings {
+    pub fn new(offsets: Vec<IVec2>) -> Self {
+        RuleGenSettings {
+            offsets,
+            border: SampleBorder::default(),
+            palette: PaletteMode::default(),
+            transparent_holes: true,
+            hole_color: None,
+            hole_mode: HoleMode::default(),
+            record_sources: false,
+        }
+    }
     
-    let mut value_colors = vec![];
-    let mut rules = vec![];
+    pub fn with_border(mut self, border: SampleBorder) -> Self {
+        self.border = border;
+        self
+    }
+    
+    pub fn with_palette(mut self, palette: PaletteMode) -> Self {
+        self.palette = palette;
+        self
+    }
+    
+    pub fn with_holes(mut self, hole_color: Option<ValueColor>, hole_mode: HoleMode) -> Self {
+        self.hole_color = hole_color;
+        self.hole_mode = hole_mode;
+        self
+    }
+    
+    pub fn with_sources(mut self) -> Self {
+        self.record_sources = true;
+        self
+    }
+    
+    pub fn is_hole(&self, pixel: Rgba<u8>) -> bool {
+        (self.transparent_holes && pixel.0[3] == 0) 
+            || self.hole_color.is_some_and(|c| c == ValueColor::from_rgba(pixel))
+    }
+}
+
+impl RuleGenSample {
+    pub fn new(path: &str) -> Self {
+        RuleGenSample {
+            path: path.to_owned(),
+            weight: 1.0,
+        }
+    }
+    
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+pub fn gen_rules_from_image(path: &str, settings: &RuleGenSettings) -> OctaResult<(Vec<Rule>, Vec<ValueColor>)> {
+    gen_rules_from_images(&[RuleGenSample::new(path)], settings)
+}
+
+/// Learns one rule set from all samples. Colors map to the same `Value` in every sample.
+pub fn gen_rules_from_images(samples: &[RuleGenSample], settings: &RuleGenSettings) -> OctaResult<(Vec<Rule>, Vec<ValueColor>)> {
+    let mut images = vec![];
+    for sample in samples.iter() {
+        images.push(ImageReader::open(&sample.path)?.decode()?);
+    }
+    
+    let is_hole = |pixel| settings.is_hole(pixel);
+    let value_colors = build_palette(&images, &settings.palette, &is_hole)?;
+    if value_colors.len() > MAX_VALUES {
+        bail!("Samples have {} colors, only {MAX_VALUES} values are supported", value_colors.len());
+    }
     
-    for x in 1..(img.width() -1) {
-        for y in 1..(img.height() -1) {
-            let pixel = img.get_pixel(x, y);
-            let value_color = ValueColor::from_rgba(pixel);
+    let loaded_samples: Vec<_> = images.iter()
+        .map(|img| Sample::from_image(img, &value_colors, &is_hole))
+        .collect();
+    
+    let mut rules: Vec<Rule> = (0..value_colors.len())
+        .map(|i| Rule::new(Value::from_value_nr(i as ValueNr)))
+        .collect();
+    
+    for (i, (sample, loaded_sample)) in samples.iter().zip(loaded_samples.iter()).enumerate() {
+        learn_rules(loaded_sample, i, sample.weight, settings, &mut rules)?;
+    }
+    
+    let value_colors = to_value_colors(value_colors);
+    
+    info!("Color: {value_colors:?}");
+    info!("Rules: {}", RuleStats::new(&rules, 0));
+    
+    Ok((rules, value_colors))
+}
+
+/// Learns rules from a grid using its `Value` numbering. `VALUE_NONE` nodes are treated as holes.
+pub fn gen_rules_from_grid(grid: &Grid, num_values: usize, settings: &RuleGenSettings) -> OctaResult<Vec<Rule>> {
+    let sample = Sample::from_grid(grid);
+    if let Some(nr) = sample.data.iter().flatten().find(|nr| **nr as usize >= num_values) {
+        bail!("Grid contains value {nr}, but only {num_values} values are known");
+    }
+    
+    let mut rules: Vec<Rule> = (0..num_values)
+        .map(|i| Rule::new(Value::from_value_nr(i as ValueNr)))
+        .collect();
+    
+    learn_rules(&sample, 0, 1.0, settings, &mut rules)?;
+    
+    Ok(rules)
+}
+
+/// Draws every node in the color of its value, empty nodes stay transparent.
+pub fn grid_to_image(grid: &Grid, value_colors: &[ValueColor]) -> RgbaImage {
+    RgbaImage::from_fn(GRID_SIZE as u32, GRID_SIZE as u32, |x, y| {
+        let value = grid.nodes[get_node_index_from_pos(ivec2(x as i32, y as i32))];
+        if value.is_none() {
+            return Rgba([0, 0, 0, 0]);
+        }
+        
+        value_colors[value.color_index as usize].to_rgba()
+    })
+}
+
+pub fn get_offset_radius(offsets: &[IVec2]) -> i32 {
+    offsets.iter()
+        .map(|o| o.x.abs().max(o.y.abs()))
+        .max()
+        .unwrap_or(0)
+}
+
+fn learn_rules(sample: &Sample, sample_index: usize, weight: f32, settings: &RuleGenSettings, rules: &mut [Rule]) -> OctaResult<()> {
+    if sample.size.cmple(IVec2::ZERO).any() {
+        bail!("Sample is empty");
+    }
+    
+    let (min, max) = match settings.border {
+        SampleBorder::Skip => {
+            let radius = get_offset_radius(&settings.offsets);
+            if sample.size.cmple(IVec2::splat(radius * 2)).any() {
+                bail!("Neighborhood radius {radius} is too large for a {}x{} sample", sample.size.x, sample.size.y);
+            }
             
-            let index = value_colors.iter().position(|c| *c == value_color).unwrap_or_else(|| {
-                let i = value_colors.len();
-                value_colors.push(value_color);
-                rules.push(Rule::new(Value::from_value_nr(i as ValueNr)));
-                i
-            });
+            (IVec2::splat(radius), sample.size - IVec2::splat(radius))
+        }
+        SampleBorder::Wrap | SampleBorder::Clamp => (IVec2::ZERO, sample.size),
+    };
+    
+    for x in min.x..max.x {
+        for y in min.y..max.y {
+            let pos = ivec2(x, y);
+            let Some(value_nr) = sample.get(pos) else {
+                continue
+            };
             
             let mut rule_req = RuleReq::new();
-            
-            for offset in offsets.iter() {
-                let req_pos = ivec2(x as i32, y as i32) + *offset;
-                if req_pos.x < 0 || req_pos.y < 0 || req_pos.x >= img.width() as i32 || req_pos.y >= img.height() as i32 {
-                    unreachable!()
+            let mut touches_hole = false;
+            for offset in settings.offsets.iter() {
+                match sample.get_with_border(pos + *offset, settings.border) {
+                    Some(req_value_nr) => rule_req.reqs.push((*offset, Value::from_value_nr(req_value_nr))),
+                    None => touches_hole = true,
                 }
-
-                let req_pixel = img.get_pixel(req_pos.x as u32, req_pos.y as u32);
-                let req_value_color = ValueColor::from_rgba(req_pixel);
-                let reg_index = value_colors.iter().position(|c| *c == req_value_color).unwrap_or_else(|| {
-                    let i = value_colors.len();
-                    value_colors.push(req_value_color);
-                    rules.push(Rule::new(Value::from_value_nr(i as ValueNr)));
-                    i
-                });
-                
-                
-                
-                let reg_value = Value::from_value_nr(reg_index as ValueNr);
-                
-                rule_req.reqs.push((*offset, reg_value));
             }
             
-            if !rules[index].reqs.contains(&rule_req) {
-                rules[index].reqs.push(rule_req);
+            if touches_hole && (settings.hole_mode == HoleMode::Skip || rule_req.reqs.is_empty()) {
+                continue
             }
+            
+            let source = settings.record_sources.then(|| RuleSource::new(sample_index, pos));
+            rules[value_nr as usize].add_req(rule_req, weight, source);
         }
     }
     
-    value_colors.insert(0, ValueColor::new(1, 0, 0));
-    
-    info!("Color: {value_colors:?}");
-    info!("Rules: {rules:?}");
-    
-    Ok((rules, value_colors))
+    Ok(())
 }
-
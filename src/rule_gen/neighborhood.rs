@@ -0,0 +1,95 @@
+use std::str::FromStr;
+use image::{GenericImageView, ImageReader, Pixel};
+use octa_force::anyhow::{anyhow, bail, Error};
+use octa_force::glam::{ivec2, IVec2};
+use octa_force::OctaResult;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Neighborhood {
+    /// The 4 direct neighbors.
+    VonNeumann,
+    /// The 8 direct and diagonal neighbors.
+    Moore,
+    /// All offsets with a manhattan distance up to the radius.
+    Diamond(i32),
+    /// All offsets with a chebyshev distance up to the radius.
+    Square(i32),
+    Custom(Vec<IVec2>),
+}
+
+impl Neighborhood {
+    pub fn get_offsets(&self) -> Vec<IVec2> {
+        match self {
+            Neighborhood::VonNeumann => Neighborhood::Diamond(1).get_offsets(),
+            Neighborhood::Moore => Neighborhood::Square(1).get_offsets(),
+            Neighborhood::Diamond(radius) => get_offsets_in_radius(*radius, |o| o.x.abs() + o.y.abs() <= *radius),
+            Neighborhood::Square(radius) => get_offsets_in_radius(*radius, |_| true),
+            Neighborhood::Custom(offsets) => offsets.to_owned(),
+        }
+    }
+    
+    /// Loads a mask with an odd size. The center pixel is the cell itself, every other bright and opaque pixel is an offset.
+    pub fn from_mask(path: &str) -> OctaResult<Self> {
+        let img = ImageReader::open(path)?.decode()?;
+        if img.width() % 2 == 0 || img.height() % 2 == 0 {
+            bail!("Neighborhood mask {path} must have an odd size");
+        }
+        
+        let center = ivec2(img.width() as i32 / 2, img.height() as i32 / 2);
+        let mut offsets = vec![];
+        for x in 0..img.width() {
+            for y in 0..img.height() {
+                let pixel = img.get_pixel(x, y);
+                let offset = ivec2(x as i32, y as i32) - center;
+                
+                if offset != IVec2::ZERO && pixel.0[3] > 127 && pixel.to_luma().0[0] > 127 {
+                    offsets.push(offset);
+                }
+            }
+        }
+        
+        Ok(Neighborhood::Custom(offsets))
+    }
+}
+
+/// Parses `von_neumann`, `moore`, `diamond <radius>`, `square <radius>` or `mask <path>`.
+impl FromStr for Neighborhood {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let arg = parts.next();
+        
+        let radius = || -> Result<i32, Error> {
+            let radius = arg.ok_or_else(|| anyhow!("Neighborhood {name} needs a radius"))?.parse::<i32>()?;
+            if radius < 1 {
+                bail!("Neighborhood radius must be at least 1");
+            }
+            Ok(radius)
+        };
+        
+        match name.as_str() {
+            "von_neumann" => Ok(Neighborhood::VonNeumann),
+            "moore" => Ok(Neighborhood::Moore),
+            "diamond" => Ok(Neighborhood::Diamond(radius()?)),
+            "square" => Ok(Neighborhood::Square(radius()?)),
+            "mask" => Neighborhood::from_mask(arg.ok_or_else(|| anyhow!("Neighborhood mask needs a path"))?),
+            _ => bail!("Unknown neighborhood {s}"),
+        }
+    }
+}
+
+fn get_offsets_in_radius(radius: i32, filter: impl Fn(IVec2) -> bool) -> Vec<IVec2> {
+    let mut offsets = vec![];
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            let offset = ivec2(x, y);
+            if offset != IVec2::ZERO && filter(offset) {
+                offsets.push(offset);
+            }
+        }
+    }
+    
+    offsets
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::slice;
+use image::ImageReader;
+use octa_force::anyhow::bail;
+use octa_force::glam::{ivec2, IVec2};
+use octa_force::log::info;
+use octa_force::OctaResult;
+use crate::rule_gen::SampleBorder;
+use crate::rule_gen::palette::{build_palette, PaletteMode};
+use crate::rule_gen::sample::Sample;
+use crate::rules::{Rule, RuleReq};
+use crate::value::{to_value_colors, Value, ValueColor, ValueNr, MAX_VALUES};
+
+#[derive(Clone, Debug)]
+pub struct OverlappingSettings {
+    pub n: i32,
+    pub periodic_input: bool,
+    /// Number of rotations and reflections of the sample to learn from, 1 to 8.
+    pub symmetry: usize,
+    /// Offsets between patterns that become rule reqs. All offsets must be smaller than `n`.
+    pub offsets: Vec<IVec2>,
+    pub palette: PaletteMode,
+}
+
+/// Classic overlapping WFC model. Every NxN pattern of the sample is a `Value`.
+/// Two patterns may be neighbors at an offset if they agree on their overlapping pixels.
+pub struct OverlappingModel {
+    pub n: i32,
+    pub patterns: Vec<Vec<ValueNr>>,
+    pub colors: Vec<ValueColor>,
+    pub rules: Vec<Rule>,
+}
+
+impl OverlappingSettings {
+    pub fn new(n: i32) -> Self {
+        OverlappingSettings {
+            n,
+            periodic_input: true,
+            symmetry: 8,
+            offsets: vec![
+                ivec2(-1, 0),
+                ivec2(1, 0),
+                ivec2(0, -1),
+                ivec2(0, 1),
+            ],
+            palette: PaletteMode::default(),
+        }
+    }
+}
+
+impl OverlappingModel {
+    pub fn from_image(path: &str, settings: &OverlappingSettings) -> OctaResult<Self> {
+        let n = settings.n;
+        if n < 1 {
+            bail!("Pattern size must be at least 1");
+        }
+        if settings.offsets.iter().any(|o| o.x.abs() >= n || o.y.abs() >= n) {
+            bail!("Offsets must be smaller than the pattern size {n}");
+        }
+        
+        let img = ImageReader::open(path)?.decode()?;
+        let colors = build_palette(slice::from_ref(&img), &settings.palette, &|_| false)?;
+        let sample = Sample::from_image(&img, &colors, &|_| false);
+        
+        if !settings.periodic_input && sample.size.cmplt(IVec2::splat(n)).any() {
+            bail!("Sample is smaller than the pattern size {n}");
+        }
+        
+        let mut patterns = vec![];
+        let mut pattern_indices = HashMap::new();
+        let mut pattern_counts = vec![];
+        for variant in sample.symmetry_variants(settings.symmetry) {
+            let size = if settings.periodic_input { 
+                variant.size 
+            } else { 
+                variant.size - IVec2::splat(n - 1) 
+            };
+            
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let pattern = get_pattern(&variant, ivec2(x, y), n);
+                    let index = *pattern_indices.entry(pattern).or_insert_with_key(|pattern: &Vec<ValueNr>| {
+                        patterns.push(pattern.to_owned());
+                        pattern_counts.push(0);
+                        patterns.len() - 1
+                    });
+                    
+                    pattern_counts[index] += 1;
+                }
+            }
+        }
+        
+        if patterns.len() > MAX_VALUES {
+            bail!("Sample has {} patterns, only {MAX_VALUES} values are supported", patterns.len());
+        }
+        
+        let mut model = OverlappingModel {
+            n,
+            patterns,
+            colors,
+            rules: vec![],
+        };
+        
+        // Like the tiled model every req is a single neighbor pattern that agrees at its offset,
+        // so a pattern needs an agreeing neighbor at every offset and not a whole learned neighborhood.
+        // A req is weighted by how often its neighbor pattern was seen, the pattern itself by its own count.
+        let num_patterns = model.patterns.len();
+        for (p, count) in pattern_counts.iter().enumerate() {
+            let mut rule = Rule::new_pairwise(Value::from_value_nr(p as ValueNr));
+            rule.set_value_weight(*count as f32);
+            
+            for offset in settings.offsets.iter() {
+                for q in (0..num_patterns).filter(|q| model.agrees(p as ValueNr, *q as ValueNr, *offset)) {
+                    let mut rule_req = RuleReq::new();
+                    rule_req.reqs.push((*offset, Value::from_value_nr(q as ValueNr)));
+                    rule.add_req(rule_req, pattern_counts[q] as f32, None);
+                }
+            }
+            
+            model.rules.push(rule);
+        }
+        
+        info!("Overlapping model: {num_patterns} patterns");
+        
+        Ok(model)
+    }
+    
+    /// Checks if pattern `b` placed at `offset` from pattern `a` agrees on the overlapping pixels.
+    pub fn agrees(&self, a: ValueNr, b: ValueNr, offset: IVec2) -> bool {
+        let n = self.n;
+        let a = &self.patterns[a as usize];
+        let b = &self.patterns[b as usize];
+        
+        let (x_min, x_max) = if offset.x < 0 { (0, offset.x + n) } else { (offset.x, n) };
+        let (y_min, y_max) = if offset.y < 0 { (0, offset.y + n) } else { (offset.y, n) };
+        
+        for y in y_min..y_max {
+            for x in x_min..x_max {
+                if a[(x + n * y) as usize] != b[(x - offset.x + n * (y - offset.y)) as usize] {
+                    return false;
+                }
+            }
+        }
+        
+        true
+    }
+    
+    /// Color of every pattern value as used by the renderer, the top left pixel of the pattern.
+    pub fn get_value_colors(&self) -> Vec<ValueColor> {
+        to_value_colors(self.patterns.iter().map(|p| self.colors[p[0] as usize]))
+    }
+}
+
+fn get_pattern(sample: &Sample, pos: IVec2, n: i32) -> Vec<ValueNr> {
+    let mut pattern = Vec::with_capacity((n * n) as usize);
+    for y in 0..n {
+        for x in 0..n {
+            pattern.push(sample.get_with_border(pos + ivec2(x, y), SampleBorder::Wrap).unwrap());
+        }
+    }
+    
+    pattern
+}
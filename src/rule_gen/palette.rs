@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use image::{DynamicImage, GenericImageView, Rgba};
+use octa_force::anyhow::bail;
+use octa_force::glam::Vec3;
+use octa_force::OctaResult;
+use crate::value::ValueColor;
+
+const K_MEANS_ITERATIONS: usize = 32;
+
+/// How the colors of the samples are reduced to the palette that becomes the `Value`s.
+#[derive(Clone, Debug, Default)]
+pub enum PaletteMode {
+    /// Every distinct RGB color is its own entry.
+    #[default]
+    Exact,
+    /// Colors within this RGB distance of an earlier entry are merged into it.
+    Threshold(f32),
+    KMeans(usize),
+    MedianCut(usize),
+    /// Snap every pixel to the nearest of these colors.
+    Fixed(Vec<ValueColor>),
+}
+
+pub fn build_palette(images: &[DynamicImage], mode: &PaletteMode, is_hole: &dyn Fn(Rgba<u8>) -> bool) -> OctaResult<Vec<ValueColor>> {
+    if matches!(mode, PaletteMode::KMeans(0) | PaletteMode::MedianCut(0)) {
+        bail!("Palette size must be at least 1");
+    }
+    
+    let colors = count_colors(images, is_hole);
+    
+    let palette = match mode {
+        PaletteMode::Exact => colors.into_iter().map(|(c, _)| c).collect(),
+        PaletteMode::Threshold(distance) => threshold_palette(&colors, *distance),
+        PaletteMode::KMeans(size) => k_means_palette(&colors, *size),
+        PaletteMode::MedianCut(size) => median_cut_palette(colors, *size),
+        PaletteMode::Fixed(palette) => palette.to_owned(),
+    };
+    
+    if palette.is_empty() {
+        bail!("Palette is empty");
+    }
+    
+    Ok(palette)
+}
+
+pub fn get_nearest_index(palette: &[ValueColor], color: ValueColor) -> usize {
+    palette.iter()
+        .enumerate()
+        .min_by_key(|(_, c)| c.distance_squared(&color))
+        .map(|(i, _)| i)
+        .unwrap()
+}
+
+/// Distinct colors of all images with their pixel count, in order of first appearance. Holes are left out.
+fn count_colors(images: &[DynamicImage], is_hole: &dyn Fn(Rgba<u8>) -> bool) -> Vec<(ValueColor, usize)> {
+    let mut colors = vec![];
+    let mut indices = HashMap::new();
+    
+    for img in images.iter() {
+        for (_, _, pixel) in img.pixels() {
+            if is_hole(pixel) {
+                continue;
+            }
+            
+            let color = ValueColor::from_rgba(pixel);
+            let index = *indices.entry(color).or_insert_with(|| {
+                colors.push((color, 0));
+                colors.len() - 1
+            });
+            colors[index].1 += 1;
+        }
+    }
+    
+    colors
+}
+
+fn threshold_palette(colors: &[(ValueColor, usize)], distance: f32) -> Vec<ValueColor> {
+    let max_distance_squared = (distance * distance) as u32;
+    
+    let mut palette: Vec<ValueColor> = vec![];
+    for (color, _) in colors.iter() {
+        if palette.iter().all(|c| c.distance_squared(color) > max_distance_squared) {
+            palette.push(*color);
+        }
+    }
+    
+    palette
+}
+
+fn k_means_palette(colors: &[(ValueColor, usize)], size: usize) -> Vec<ValueColor> {
+    if colors.len() <= size {
+        return colors.iter().map(|(c, _)| *c).collect();
+    }
+    
+    // Farthest point initialization starting at the most common color keeps the result deterministic.
+    let mut centers = vec![colors.iter().max_by_key(|(_, count)| *count).unwrap().0.as_vec3()];
+    while centers.len() < size {
+        let next = colors.iter()
+            .map(|(c, _)| c.as_vec3())
+            .max_by(|a, b| {
+                let da = centers.iter().map(|c| c.distance_squared(*a)).fold(f32::MAX, f32::min);
+                let db = centers.iter().map(|c| c.distance_squared(*b)).fold(f32::MAX, f32::min);
+                da.total_cmp(&db)
+            })
+            .unwrap();
+        centers.push(next);
+    }
+    
+    for _ in 0..K_MEANS_ITERATIONS {
+        let mut sums = vec![(Vec3::ZERO, 0.0); size];
+        for (color, count) in colors.iter() {
+            let v = color.as_vec3();
+            let nearest = centers.iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.distance_squared(v).total_cmp(&b.distance_squared(v)))
+                .map(|(i, _)| i)
+                .unwrap();
+            
+            sums[nearest].0 += v * *count as f32;
+            sums[nearest].1 += *count as f32;
+        }
+        
+        let new_centers: Vec<Vec3> = sums.iter()
+            .zip(centers.iter())
+            .map(|((sum, count), center)| if *count > 0.0 { *sum / *count } else { *center })
+            .collect();
+        
+        let done = new_centers == centers;
+        centers = new_centers;
+        if done {
+            break;
+        }
+    }
+    
+    dedup(centers.into_iter().map(ValueColor::from_vec3).collect())
+}
+
+fn median_cut_palette(colors: Vec<(ValueColor, usize)>, size: usize) -> Vec<ValueColor> {
+    let mut boxes = vec![colors];
+    
+    while boxes.len() < size {
+        let (index, axis, range) = boxes.iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (axis, range) = get_widest_axis(b);
+                (i, axis, range)
+            })
+            .max_by(|a, b| a.2.total_cmp(&b.2))
+            .unwrap_or((0, 0, 0.0));
+        
+        if range <= 0.0 {
+            break;
+        }
+        
+        let mut b = boxes.swap_remove(index);
+        b.sort_by(|x, y| x.0.as_vec3()[axis].total_cmp(&y.0.as_vec3()[axis]));
+        
+        let total: usize = b.iter().map(|(_, count)| count).sum();
+        let mut seen = 0;
+        let mut split = b.len() - 1;
+        for (i, (_, count)) in b.iter().enumerate() {
+            seen += count;
+            if seen * 2 >= total {
+                split = (i + 1).min(b.len() - 1);
+                break;
+            }
+        }
+        
+        let upper = b.split_off(split);
+        boxes.push(b);
+        boxes.push(upper);
+    }
+    
+    // Only happens if every pixel is a hole, which leaves the palette empty.
+    dedup(boxes.iter()
+        .filter(|b| !b.is_empty())
+        .map(|b| {
+            let (sum, count) = b.iter().fold((Vec3::ZERO, 0.0), |(sum, count), (c, n)| {
+                (sum + c.as_vec3() * *n as f32, count + *n as f32)
+            });
+            ValueColor::from_vec3(sum / count)
+        })
+        .collect())
+}
+
+fn get_widest_axis(colors: &[(ValueColor, usize)]) -> (usize, f32) {
+    let min = colors.iter().fold(Vec3::splat(f32::MAX), |m, (c, _)| m.min(c.as_vec3()));
+    let max = colors.iter().fold(Vec3::splat(f32::MIN), |m, (c, _)| m.max(c.as_vec3()));
+    let range = max - min;
+    
+    (0..3)
+        .map(|i| (i, range[i]))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+}
+
+fn dedup(palette: Vec<ValueColor>) -> Vec<ValueColor> {
+    let mut result = vec![];
+    for color in palette {
+        if !result.contains(&color) {
+            result.push(color);
+        }
+    }
+    
+    result
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use image::{DynamicImage, GenericImageView, Rgba};
+use octa_force::glam::{ivec2, IVec2};
+use crate::grid::{get_node_index_from_pos, Grid, GRID_SIZE, NODES_PER_GRID};
+use crate::rule_gen::palette::get_nearest_index;
+use crate::rule_gen::SampleBorder;
+use crate::value::{ValueColor, ValueNr};
+
+/// Value numbers of a sample. `None` cells are holes that never contribute to a rule.
+#[derive(Clone)]
+pub struct Sample {
+    pub size: IVec2,
+    pub data: Vec<Option<ValueNr>>,
+}
+
+impl Sample {
+    /// Maps every pixel to the nearest palette entry.
+    pub fn from_image(img: &DynamicImage, palette: &[ValueColor], is_hole: &dyn Fn(Rgba<u8>) -> bool) -> Self {
+        let size = ivec2(img.width() as i32, img.height() as i32);
+        let mut data = Vec::with_capacity((size.x * size.y) as usize);
+        let mut indices = HashMap::new();
+        
+        for y in 0..img.height() {
+            for x in 0..img.width() {
+                let pixel = img.get_pixel(x, y);
+                if is_hole(pixel) {
+                    data.push(None);
+                    continue;
+                }
+                
+                let value_color = ValueColor::from_rgba(pixel);
+                let index = *indices.entry(value_color)
+                    .or_insert_with(|| get_nearest_index(palette, value_color));
+                
+                data.push(Some(index as ValueNr));
+            }
+        }
+        
+        Sample { size, data }
+    }
+    
+    pub fn from_grid(grid: &Grid) -> Self {
+        let size = IVec2::splat(GRID_SIZE as i32);
+        let mut data = Vec::with_capacity(NODES_PER_GRID);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let value = grid.nodes[get_node_index_from_pos(ivec2(x, y))];
+                data.push(value.is_some().then(|| value.get_value_nr()));
+            }
+        }
+        
+        Sample { size, data }
+    }
+    
+    pub fn get(&self, pos: IVec2) -> Option<ValueNr> {
+        self.data[(pos.y * self.size.x + pos.x) as usize]
+    }
+    
+    pub fn get_with_border(&self, pos: IVec2, border: SampleBorder) -> Option<ValueNr> {
+        let pos = match border {
+            SampleBorder::Skip => {
+                if pos.cmplt(IVec2::ZERO).any() || pos.cmpge(self.size).any() {
+                    return None;
+                }
+                pos
+            }
+            SampleBorder::Wrap => ivec2(pos.x.rem_euclid(self.size.x), pos.y.rem_euclid(self.size.y)),
+            SampleBorder::Clamp => pos.clamp(IVec2::ZERO, self.size - IVec2::ONE),
+        };
+        
+        self.get(pos)
+    }
+    
+    /// Rotates the sample by 90 degrees counterclockwise.
+    pub fn rotated(&self) -> Sample {
+        let size = ivec2(self.size.y, self.size.x);
+        let mut data = Vec::with_capacity(self.data.len());
+        for y in 0..size.y {
+            for x in 0..size.x {
+                data.push(self.get(ivec2(self.size.x - 1 - y, x)));
+            }
+        }
+        
+        Sample { size, data }
+    }
+    
+    /// Mirrors the sample along the vertical axis.
+    pub fn reflected(&self) -> Sample {
+        let mut data = Vec::with_capacity(self.data.len());
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                data.push(self.get(ivec2(self.size.x - 1 - x, y)));
+            }
+        }
+        
+        Sample { size: self.size, data }
+    }
+    
+    /// Returns the first `symmetry` (1 to 8) rotations and reflections of the sample.
+    pub fn symmetry_variants(&self, symmetry: usize) -> Vec<Sample> {
+        let mut variants = vec![self.clone()];
+        while variants.len() < symmetry.clamp(1, 8) {
+            let last = variants.len() - 1;
+            let next = if variants.len() % 2 == 1 {
+                variants[last].reflected()
+            } else {
+                variants[last - 1].rotated()
+            };
+            variants.push(next);
+        }
+        
+        variants
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use std::fmt::Write;
+use octa_force::glam::IVec2;
+use crate::rules::Rule;
+use crate::value::ValueNr;
+
+#[derive(Clone, Debug)]
+pub struct RuleStats {
+    pub num_values: usize,
+    pub num_reqs: usize,
+    pub num_occurrences: usize,
+    pub values: Vec<ValueStats>,
+    /// `(occurrences, number of reqs seen that often)`, sorted by occurrences.
+    pub frequency_histogram: Vec<(usize, usize)>,
+    pub most_common: Vec<ReqStats>,
+    pub least_common: Vec<ReqStats>,
+    /// Share of reqs that were seen only once.
+    pub singleton_share: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ValueStats {
+    pub value_nr: ValueNr,
+    pub num_reqs: usize,
+    pub num_occurrences: usize,
+    pub num_singletons: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReqStats {
+    pub value_nr: ValueNr,
+    pub req_index: usize,
+    /// Value at every offset of the req, `None` for an empty value.
+    pub neighborhood: Vec<(IVec2, Option<ValueNr>)>,
+    pub count: usize,
+    pub weight: f32,
+}
+
+impl RuleStats {
+    /// `num_extremes` is the number of most and least common reqs kept.
+    pub fn new(rules: &[Rule], num_extremes: usize) -> Self {
+        let mut reqs = vec![];
+        let mut values = vec![];
+        for rule in rules.iter() {
+            let value_nr = rule.value.get_value_nr();
+            for (req_index, count) in rule.get_counts().iter().enumerate() {
+                reqs.push(ReqStats {
+                    value_nr,
+                    req_index,
+                    neighborhood: rule.get_reqs()[req_index].reqs.iter()
+                        .map(|(offset, value)| (*offset, value.is_some().then(|| value.get_value_nr())))
+                        .collect(),
+                    count: *count,
+                    weight: rule.get_weights()[req_index],
+                });
+            }
+            
+            values.push(ValueStats {
+                value_nr,
+                num_reqs: rule.get_reqs().len(),
+                num_occurrences: rule.get_counts().iter().sum(),
+                num_singletons: rule.get_counts().iter().filter(|c| **c == 1).count(),
+            });
+        }
+        
+        let mut frequency_histogram: Vec<(usize, usize)> = vec![];
+        reqs.sort_by_key(|r| r.count);
+        for req in reqs.iter() {
+            match frequency_histogram.last_mut() {
+                Some((count, num)) if *count == req.count => *num += 1,
+                _ => frequency_histogram.push((req.count, 1)),
+            }
+        }
+        
+        let num_singletons: usize = values.iter().map(|v| v.num_singletons).sum();
+        let singleton_share = if reqs.is_empty() { 0.0 } else { num_singletons as f32 / reqs.len() as f32 };
+        
+        let least_common = reqs.iter().take(num_extremes).cloned().collect();
+        let most_common = reqs.iter().rev().take(num_extremes).cloned().collect();
+        
+        RuleStats {
+            num_values: rules.len(),
+            num_reqs: reqs.len(),
+            num_occurrences: values.iter().map(|v| v.num_occurrences).sum(),
+            values,
+            frequency_histogram,
+            most_common,
+            least_common,
+            singleton_share,
+        }
+    }
+    
+    pub fn to_json(&self) -> String {
+        let reqs_to_json = |reqs: &[ReqStats]| -> String {
+            reqs.iter()
+                .map(|r| {
+                    let neighborhood = r.neighborhood.iter()
+                        .map(|(offset, value)| format!(r#"{{"offset":[{},{}],"value":{}}}"#, 
+                                                       offset.x, offset.y, value.map_or("null".to_owned(), |v| v.to_string())))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    format!(r#"{{"value":{},"req":{},"count":{},"weight":{},"neighborhood":[{neighborhood}]}}"#, 
+                            r.value_nr, r.req_index, r.count, json_f32(r.weight))
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        
+        let values = self.values.iter()
+            .map(|v| format!(r#"{{"value":{},"reqs":{},"occurrences":{},"singletons":{}}}"#, 
+                             v.value_nr, v.num_reqs, v.num_occurrences, v.num_singletons))
+            .collect::<Vec<_>>()
+            .join(",");
+        
+        let histogram = self.frequency_histogram.iter()
+            .map(|(count, num)| format!(r#"{{"occurrences":{count},"reqs":{num}}}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        
+        format!(
+            r#"{{"num_values":{},"num_reqs":{},"num_occurrences":{},"singleton_share":{},"values":[{values}],"frequency_histogram":[{histogram}],"most_common":[{}],"least_common":[{}]}}"#,
+            self.num_values, self.num_reqs, self.num_occurrences, json_f32(self.singleton_share),
+            reqs_to_json(&self.most_common), reqs_to_json(&self.least_common),
+        )
+    }
+    
+    /// One row per value.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("value,reqs,occurrences,singletons\n");
+        for v in self.values.iter() {
+            writeln!(csv, "{},{},{},{}", v.value_nr, v.num_reqs, v.num_occurrences, v.num_singletons).unwrap();
+        }
+        
+        csv
+    }
+    
+    /// One row per occurrence count.
+    pub fn histogram_to_csv(&self) -> String {
+        let mut csv = String::from("occurrences,reqs\n");
+        for (count, num) in self.frequency_histogram.iter() {
+            writeln!(csv, "{count},{num}").unwrap();
+        }
+        
+        csv
+    }
+}
+
+/// JSON has no inf or NaN.
+fn json_f32(value: f32) -> String {
+    if value.is_finite() { value.to_string() } else { "null".to_owned() }
+}
+
+impl fmt::Display for RuleStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} values, {} reqs from {} occurrences, {:.1}% seen once", 
+               self.num_values, self.num_reqs, self.num_occurrences, self.singleton_share * 100.0)?;
+        
+        for (name, reqs) in [("Most common", &self.most_common), ("Least common", &self.least_common)] {
+            if reqs.is_empty() {
+                continue;
+            }
+            
+            write!(f, "\n{name}:")?;
+            for r in reqs.iter() {
+                write!(f, "\n    value {} req {} seen {}x:", r.value_nr, r.req_index, r.count)?;
+                for (offset, value) in r.neighborhood.iter() {
+                    match value {
+                        Some(value) => write!(f, " {offset}={value}")?,
+                        None => write!(f, " {offset}=none")?,
+                    }
+                }
+            }
+        }
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::{imageops, ImageReader, RgbaImage};
+use octa_force::anyhow::{anyhow, bail};
+use octa_force::glam::{ivec2, IVec2};
+use octa_force::log::info;
+use octa_force::OctaResult;
+use crate::grid::{get_node_index_from_pos, Grid, GRID_SIZE};
+use crate::rules::{Rule, RuleReq};
+use crate::value::{to_value_colors, Value, ValueColor, ValueNr, MAX_VALUES};
+
+/// Offsets of the four directions in the order WaveFunctionCollapse uses them.
+const DIRECTIONS: [IVec2; 4] = [ivec2(-1, 0), ivec2(0, 1), ivec2(1, 0), ivec2(0, -1)];
+
+/// Simple tiled model loaded from a WaveFunctionCollapse tileset XML. Every tile variant is a `Value`.
+/// The rules are pairwise, one req per allowed neighbor of each direction.
+pub struct TiledModel {
+    pub tile_size: u32,
+    pub tile_names: Vec<String>,
+    pub tile_images: Vec<RgbaImage>,
+    /// Weight of each tile variant. It is the value weight of its rule and the weight of every req that allows it as a neighbor.
+    pub weights: Vec<f32>,
+    pub rules: Vec<Rule>,
+}
+
+/// Symmetry class of a tile, given by the maps of a 90 degree rotation and a reflection on its variants.
+struct Symmetry {
+    cardinality: usize,
+    rotate: fn(usize) -> usize,
+    reflect: fn(usize) -> usize,
+}
+
+impl Symmetry {
+    fn from_char(c: char) -> OctaResult<Self> {
+        Ok(match c {
+            'L' => Symmetry { cardinality: 4, rotate: |i| (i + 1) % 4, reflect: |i| if i % 2 == 0 { i + 1 } else { i - 1 } },
+            'T' => Symmetry { cardinality: 4, rotate: |i| (i + 1) % 4, reflect: |i| if i % 2 == 0 { i } else { 4 - i } },
+            'I' => Symmetry { cardinality: 2, rotate: |i| 1 - i, reflect: |i| i },
+            '\\' => Symmetry { cardinality: 2, rotate: |i| 1 - i, reflect: |i| 1 - i },
+            'X' => Symmetry { cardinality: 1, rotate: |i| i, reflect: |i| i },
+            _ => bail!("Unknown tile symmetry {c}, expected X, L, T, I or \\"),
+        })
+    }
+}
+
+impl TiledModel {
+    pub fn from_xml(path: &str) -> OctaResult<Self> {
+        let text = fs::read_to_string(path)?;
+        let doc = roxmltree::Document::parse(&text)?;
+        let set = doc.root_element();
+        let unique = set.attribute("unique").is_some_and(|u| u.eq_ignore_ascii_case("true"));
+        let tile_dir = get_tile_dir(Path::new(path));
+        
+        let tiles = set.children()
+            .find(|n| n.has_tag_name("tiles"))
+            .ok_or_else(|| anyhow!("Tileset {path} has no tiles"))?;
+        
+        let mut tile_names = vec![];
+        let mut tile_images: Vec<RgbaImage> = vec![];
+        let mut weights = vec![];
+        let mut actions: Vec<[usize; 8]> = vec![];
+        let mut first_occurrence = HashMap::new();
+        
+        for tile in tiles.children().filter(|n| n.has_tag_name("tile")) {
+            let name = tile.attribute("name").ok_or_else(|| anyhow!("Tile without name in {path}"))?;
+            let symmetry = match tile.attribute("symmetry") {
+                Some(s) if s.chars().count() == 1 => Symmetry::from_char(s.chars().next().unwrap())?,
+                Some(s) => bail!("Unknown symmetry {s} of tile {name} in {path}"),
+                None => Symmetry::from_char('X')?,
+            };
+            let weight = tile.attribute("weight").map(|w| w.parse::<f32>()).transpose()?.unwrap_or(1.0);
+            
+            let first = actions.len();
+            first_occurrence.insert(name.to_owned(), first);
+            
+            for t in 0..symmetry.cardinality {
+                let a = symmetry.rotate;
+                let b = symmetry.reflect;
+                actions.push([
+                    t, a(t), a(a(t)), a(a(a(t))), 
+                    b(t), b(a(t)), b(a(a(t))), b(a(a(a(t)))),
+                ].map(|i| i + first));
+                
+                tile_names.push(format!("{name} {t}"));
+                weights.push(weight);
+            }
+            
+            if unique {
+                for t in 0..symmetry.cardinality {
+                    tile_images.push(load_tile_image(&tile_dir.join(format!("{name} {t}.png")))?);
+                }
+            } else {
+                tile_images.push(load_tile_image(&tile_dir.join(format!("{name}.png")))?);
+                for t in 1..symmetry.cardinality {
+                    let image = if t < 4 {
+                        imageops::rotate270(&tile_images[first + t - 1])
+                    } else {
+                        imageops::flip_horizontal(&tile_images[first + t - 4])
+                    };
+                    tile_images.push(image);
+                }
+            }
+        }
+        
+        let num_tiles = actions.len();
+        if num_tiles > MAX_VALUES {
+            bail!("Tileset {path} has {num_tiles} tile variants, only {MAX_VALUES} values are supported");
+        }
+        
+        let tile_size = tile_images.first().map(|i| i.width()).unwrap_or(0);
+        if tile_images.iter().any(|i| i.width() != tile_size || i.height() != tile_size) {
+            bail!("Tiles of {path} are not all {tile_size}x{tile_size}");
+        }
+        
+        // dense[d][t1][t2]: t2 may be at DIRECTIONS[d] from t1.
+        let mut dense = vec![vec![vec![false; num_tiles]; num_tiles]; 2];
+        let neighbors = set.children().filter(|n| n.has_tag_name("neighbors")).flat_map(|n| n.children());
+        for neighbor in neighbors.filter(|n| n.has_tag_name("neighbor")) {
+            let parse_tile = |attribute: &str| -> OctaResult<usize> {
+                let text = neighbor.attribute(attribute).ok_or_else(|| anyhow!("Neighbor without {attribute} in {path}"))?;
+                let mut parts = text.split_whitespace();
+                let name = parts.next().unwrap_or_default();
+                let rotation = parts.next().map(|r| r.parse::<usize>()).transpose()?.unwrap_or(0);
+                let first = first_occurrence.get(name).ok_or_else(|| anyhow!("Unknown tile {name} in {path}"))?;
+                
+                Ok(actions[*first][rotation % 8])
+            };
+            
+            let l = parse_tile("left")?;
+            let d = actions[l][1];
+            let r = parse_tile("right")?;
+            let u = actions[r][1];
+            
+            dense[0][r][l] = true;
+            dense[0][actions[r][6]][actions[l][6]] = true;
+            dense[0][actions[l][4]][actions[r][4]] = true;
+            dense[0][actions[l][2]][actions[r][2]] = true;
+            
+            dense[1][u][d] = true;
+            dense[1][actions[d][6]][actions[u][6]] = true;
+            dense[1][actions[u][4]][actions[d][4]] = true;
+            dense[1][actions[d][2]][actions[u][2]] = true;
+        }
+        
+        // The two remaining directions are the opposites, so they are the transposes.
+        let transposed: Vec<_> = dense.iter()
+            .map(|d| (0..num_tiles).map(|t2| d.iter().map(|row| row[t2]).collect()).collect())
+            .collect();
+        dense.extend(transposed);
+        
+        let mut rules = vec![];
+        for t in 0..num_tiles {
+            let mut rule = Rule::new_pairwise(Value::from_value_nr(t as ValueNr));
+            rule.set_value_weight(weights[t]);
+            
+            for (d, direction) in DIRECTIONS.iter().enumerate() {
+                for t2 in (0..num_tiles).filter(|t2| dense[d][t][*t2]) {
+                    let mut rule_req = RuleReq::new();
+                    rule_req.reqs.push((*direction, Value::from_value_nr(t2 as ValueNr)));
+                    rule.add_req(rule_req, weights[t2], None);
+                }
+            }
+            
+            rules.push(rule);
+        }
+        
+        info!("Tiled model: {num_tiles} tiles");
+        
+        Ok(TiledModel {
+            tile_size,
+            tile_names,
+            tile_images,
+            weights,
+            rules,
+        })
+    }
+    
+    /// Average color of every tile value as used by the renderer.
+    pub fn get_value_colors(&self) -> Vec<ValueColor> {
+        to_value_colors(self.tile_images.iter()
+            .map(|image| {
+                let num_pixels = (image.width() * image.height()).max(1) as u64;
+                let sum = image.pixels().fold([0u64; 3], |mut sum, p| {
+                    for (s, c) in sum.iter_mut().zip(p.0) {
+                        *s += c as u64;
+                    }
+                    sum
+                });
+                
+                ValueColor::new(
+                    (sum[0] / num_pixels) as u8,
+                    (sum[1] / num_pixels) as u8,
+                    (sum[2] / num_pixels) as u8,
+                )
+            }))
+    }
+    
+    pub fn grid_to_image(&self, grid: &Grid) -> RgbaImage {
+        let size = GRID_SIZE as u32 * self.tile_size;
+        let mut image = RgbaImage::new(size, size);
+        
+        for x in 0..GRID_SIZE as i32 {
+            for y in 0..GRID_SIZE as i32 {
+                let value = grid.nodes[get_node_index_from_pos(ivec2(x, y))];
+                if value.is_none() {
+                    continue;
+                }
+                
+                let tile = &self.tile_images[value.get_value_nr() as usize];
+                imageops::replace(&mut image, tile, (x as u32 * self.tile_size) as i64, (y as u32 * self.tile_size) as i64);
+            }
+        }
+        
+        image
+    }
+}
+
+/// Tilesets either keep their tiles in a folder named like the XML file or next to a `data.xml`.
+fn get_tile_dir(xml_path: &Path) -> PathBuf {
+    let dir = xml_path.parent().unwrap_or(Path::new("."));
+    let named_dir = xml_path.file_stem().map(|stem| dir.join(stem));
+    
+    match named_dir {
+        Some(named_dir) if named_dir.is_dir() => named_dir,
+        _ => dir.to_path_buf(),
+    }
+}
+
+fn load_tile_image(path: &Path) -> OctaResult<RgbaImage> {
+    Ok(ImageReader::open(path)?.decode()?.to_rgba8())
+}
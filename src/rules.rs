@@ -4,24 +4,116 @@ use crate::value::Value;
 pub const NUM_VALUES: usize = 3;
 pub const NUM_REQS: usize = 8;
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Rule {
     pub value: Value,
-    pub reqs: Vec<RuleReq>,
+    
+    /// Every req is a single neighbor and every offset needs one of its reqs, instead of one req needing to hold as a whole.
+    /// Pairwise constraints like tile adjacencies need this, as whole neighborhoods would be the cartesian product of them.
+    pairwise: bool,
+    
+    /// Only grows through `add_req`, so the per req vectors below stay aligned with it.
+    reqs: Vec<RuleReq>,
+    
+    /// Number of occurrences of each req in the samples. Indexed like `reqs`.
+    counts: Vec<usize>,
+    /// Summed sample weight of all occurrences of each req. Indexed like `reqs`.
+    weights: Vec<f32>,
+    
+    /// Sample positions where each req was seen. Indexed like `reqs`, the list of a req is empty if sources were not recorded.
+    sources: Vec<Vec<RuleSource>>,
+    
+    /// How often the value itself should be picked. The summed req weights if not set,
+    /// which is the sample weight of the value when every occurrence adds one req.
+    value_weight: Option<f32>,
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct RuleReq {
     pub reqs: Vec<(IVec2, Value)>
 }
 
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct RuleSource {
+    pub sample: usize,
+    pub pos: IVec2,
+}
+
 impl Rule {
     pub fn new(value: Value) -> Self {
         Rule{
             value,
+            pairwise: false,
             reqs: vec![],
+            counts: vec![],
+            weights: vec![],
+            sources: vec![],
+            value_weight: None,
         }
     } 
+    
+    pub fn new_pairwise(value: Value) -> Self {
+        Rule {
+            pairwise: true,
+            ..Rule::new(value)
+        }
+    }
+    
+    pub fn is_pairwise(&self) -> bool {
+        self.pairwise
+    }
+    
+    pub fn add_req(&mut self, rule_req: RuleReq, weight: f32, source: Option<RuleSource>) -> usize {
+        debug_assert!(!self.pairwise || rule_req.reqs.len() == 1, "Reqs of pairwise rules are single neighbors");
+        
+        let index = self.reqs.iter().position(|r| *r == rule_req).unwrap_or_else(|| {
+            self.reqs.push(rule_req);
+            self.counts.push(0);
+            self.weights.push(0.0);
+            self.sources.push(vec![]);
+            self.reqs.len() - 1
+        });
+        
+        self.counts[index] += 1;
+        self.weights[index] += weight;
+        
+        if let Some(source) = source {
+            self.sources[index].push(source);
+        }
+        
+        index
+    }
+    
+    pub fn get_reqs(&self) -> &[RuleReq] {
+        &self.reqs
+    }
+    
+    pub fn get_counts(&self) -> &[usize] {
+        &self.counts
+    }
+    
+    pub fn get_weights(&self) -> &[f32] {
+        &self.weights
+    }
+    
+    pub fn get_value_weight(&self) -> f32 {
+        self.value_weight.unwrap_or_else(|| self.weights.iter().sum())
+    }
+    
+    pub fn set_value_weight(&mut self, weight: f32) {
+        self.value_weight = Some(weight);
+    }
+    
+    pub fn get_sources(&self, req_index: usize) -> &[RuleSource] {
+        &self.sources[req_index]
+    }
+    
+    pub fn get_sources_of_req(&self, rule_req: &RuleReq) -> &[RuleSource] {
+        self.reqs.iter()
+            .position(|r| r == rule_req)
+            .map(|i| self.get_sources(i))
+            .unwrap_or(&[])
+    }
 }
 
 impl RuleReq {
@@ -31,3 +123,9 @@ impl RuleReq {
         }
     }
 }
+
+impl RuleSource {
+    pub fn new(sample: usize, pos: IVec2) -> Self {
+        RuleSource { sample, pos }
+    }
+}
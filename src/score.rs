@@ -0,0 +1,45 @@
+use crate::grid_manager::WorkingGrid;
+
+/// Ranks working grids in the queue of the `GridManager`. Lower scores are expanded first.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ScoreStrategy {
+    /// `orders * 10 + set_count`
+    #[default]
+    OrdersAndSets,
+    /// Fewest pending orders.
+    FewestOrders,
+    /// Fewest nodes that were set to a different value.
+    FewestChanged,
+    /// Changed nodes so far as cost plus the weighted pending orders as estimate of the remaining changes.
+    WeightedAStar { weight: f32 },
+}
+
+impl ScoreStrategy {
+    pub const ALL: [ScoreStrategy; 4] = [
+        ScoreStrategy::OrdersAndSets,
+        ScoreStrategy::FewestOrders,
+        ScoreStrategy::FewestChanged,
+        ScoreStrategy::WeightedAStar { weight: 2.0 },
+    ];
+    
+    pub fn get_score(&self, working_grid: &WorkingGrid) -> i64 {
+        let orders = working_grid.orders.len() as i64;
+        let changed = working_grid.get_changed_count() as i64;
+        
+        match self {
+            ScoreStrategy::OrdersAndSets => orders * 10 + working_grid.set_count as i64,
+            ScoreStrategy::FewestOrders => orders,
+            ScoreStrategy::FewestChanged => changed,
+            ScoreStrategy::WeightedAStar { weight } => changed + (orders as f32 * weight).round() as i64,
+        }
+    }
+    
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            ScoreStrategy::OrdersAndSets => "orders and sets",
+            ScoreStrategy::FewestOrders => "fewest orders",
+            ScoreStrategy::FewestChanged => "fewest changed",
+            ScoreStrategy::WeightedAStar { .. } => "weighted A*",
+        }
+    }
+}
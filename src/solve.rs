@@ -0,0 +1,175 @@
+use std::time::{Duration, Instant};
+use octa_force::glam::IVec2;
+use octa_force::anyhow::bail;
+use octa_force::OctaResult;
+use crate::budget::SolveBudget;
+use crate::diagnosis::Diagnosis;
+use crate::grid::Grid;
+use crate::grid_manager::{CandidateOrder, GridManager, SolutionRanking, SolveStatus, SolverStats};
+use crate::rules::Rule;
+use crate::score::ScoreStrategy;
+use crate::value::Value;
+
+/// Settings of a headless repair, see `solve`.
+#[derive(Clone, Debug)]
+pub struct SolveOptions {
+    pub score_strategy: ScoreStrategy,
+    pub candidate_order: CandidateOrder,
+    pub random_tie_break: bool,
+    pub beam_width: Option<usize>,
+    pub max_queue_size: Option<usize>,
+    pub parallel_expansions: usize,
+    /// Threads the parallel expansions are spread over, all cores if not set.
+    pub num_threads: Option<usize>,
+    pub max_solutions: usize,
+    pub solution_ranking: SolutionRanking,
+    pub budget: SolveBudget,
+    pub locked: Vec<IVec2>,
+    pub diagnose_failures: bool,
+    /// A random seed is used if not set.
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct SolveResult {
+    /// The best solution, or the input grid if the repair was not solved.
+    pub grid: Grid,
+    /// All distinct solutions, best first.
+    pub solutions: Vec<Grid>,
+    /// Best queued working grid if the budget was exceeded.
+    pub best_partial: Option<Grid>,
+    pub status: SolveStatus,
+    pub stats: SolverStats,
+    pub diagnosis: Option<Diagnosis>,
+    pub seed: u64,
+    pub elapsed: Duration,
+}
+
+impl SolveOptions {
+    pub fn new() -> Self {
+        SolveOptions {
+            score_strategy: ScoreStrategy::default(),
+            candidate_order: CandidateOrder::default(),
+            random_tie_break: false,
+            beam_width: None,
+            max_queue_size: None,
+            parallel_expansions: 1,
+            num_threads: None,
+            max_solutions: 1,
+            solution_ranking: SolutionRanking::default(),
+            budget: SolveBudget::default(),
+            locked: vec![],
+            diagnose_failures: false,
+            seed: None,
+        }
+    }
+
+    pub fn with_score_strategy(mut self, score_strategy: ScoreStrategy) -> Self {
+        self.score_strategy = score_strategy;
+        self
+    }
+
+    pub fn with_candidate_order(mut self, candidate_order: CandidateOrder) -> Self {
+        self.candidate_order = candidate_order;
+        self
+    }
+
+    pub fn with_beam_width(mut self, beam_width: usize) -> Self {
+        self.beam_width = Some(beam_width);
+        self
+    }
+
+    pub fn with_max_queue_size(mut self, max_queue_size: usize) -> Self {
+        self.max_queue_size = Some(max_queue_size);
+        self
+    }
+
+    pub fn with_parallel_expansions(mut self, parallel_expansions: usize) -> Self {
+        self.parallel_expansions = parallel_expansions;
+        self
+    }
+
+    pub fn with_num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    pub fn with_solutions(mut self, max_solutions: usize, solution_ranking: SolutionRanking) -> Self {
+        self.max_solutions = max_solutions;
+        self.solution_ranking = solution_ranking;
+        self
+    }
+
+    pub fn with_budget(mut self, budget: SolveBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    pub fn with_locked(mut self, locked: Vec<IVec2>) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    pub fn with_diagnose_failures(mut self, diagnose_failures: bool) -> Self {
+        self.diagnose_failures = diagnose_failures;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Repairs the grid after the edits by ticking a `GridManager` until the repair ends.
+/// Without a budget this only returns once the repair is solved or unsolvable.
+/// Fails if a value has no rule or no edit is left after dropping the ones outside of the grid or on locked nodes.
+pub fn solve(grid: Grid, rules: Vec<Rule>, edits: &[(IVec2, Value)], options: &SolveOptions) -> OctaResult<SolveResult> {
+    let start = Instant::now();
+
+    let mut grid_manager = GridManager::new(grid, rules);
+    if let Some(seed) = options.seed {
+        grid_manager.set_seed(seed);
+    }
+    grid_manager.set_score_strategy(options.score_strategy);
+    grid_manager.candidate_order = options.candidate_order;
+    grid_manager.random_tie_break = options.random_tie_break;
+    grid_manager.set_beam_width(options.beam_width)?;
+    grid_manager.set_max_queue_size(options.max_queue_size)?;
+    grid_manager.parallel_expansions = options.parallel_expansions;
+    grid_manager.num_threads = options.num_threads;
+    grid_manager.max_solutions = options.max_solutions;
+    grid_manager.solution_ranking = options.solution_ranking;
+    grid_manager.budget = options.budget;
+    grid_manager.diagnose_failures = options.diagnose_failures;
+    for pos in options.locked.iter() {
+        grid_manager.lock(*pos);
+    }
+
+    grid_manager.select_values(edits)?;
+    if grid_manager.status != SolveStatus::Running {
+        bail!("All edits are outside of the grid or on locked nodes");
+    }
+    
+    while grid_manager.status == SolveStatus::Running {
+        grid_manager.tick();
+    }
+
+    let seed = grid_manager.get_seed();
+    Ok(SolveResult {
+        grid: grid_manager.grid,
+        solutions: grid_manager.done_grids,
+        best_partial: grid_manager.best_partial,
+        status: grid_manager.status,
+        stats: grid_manager.stats,
+        diagnosis: grid_manager.diagnosis,
+        seed,
+        elapsed: start.elapsed(),
+    })
+}
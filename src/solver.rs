@@ -0,0 +1,111 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use octa_force::anyhow::{bail, Error};
+use octa_force::OctaResult;
+use octa_force::glam::IVec2;
+use crate::grid::Grid;
+use crate::grid_manager::{GridManager, SolveStatus, SolverStats};
+use crate::rules::Rule;
+use crate::value::Value;
+use crate::wfc::Wfc;
+
+/// Common interface of the solvers, so they can be swapped in the visualization, the cli and benchmarks.
+pub trait Solver {
+    fn from_rules(grid: Grid, rules: Vec<Rule>, seed: u64) -> Self where Self: Sized;
+
+    fn get_name(&self) -> &'static str;
+
+    /// Starts solving the grid with the edits placed. Fails if a value has no rule.
+    fn apply_edits(&mut self, edits: &[(IVec2, Value)]) -> OctaResult<()>;
+
+    /// Does one unit of work. Returns false if there was nothing left to do.
+    fn step(&mut self) -> bool;
+
+    /// The last solved grid.
+    fn get_result(&self) -> &Grid;
+
+    fn get_status(&self) -> SolveStatus;
+
+    fn get_stats(&self) -> &SolverStats;
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum SolverKind {
+    #[default]
+    LazyRepair,
+    Wfc,
+}
+
+impl SolverKind {
+    pub const ALL: [SolverKind; 2] = [SolverKind::LazyRepair, SolverKind::Wfc];
+
+    pub fn create(&self, grid: Grid, rules: Vec<Rule>, seed: u64) -> Box<dyn Solver> {
+        match self {
+            SolverKind::LazyRepair => Box::new(GridManager::from_rules(grid, rules, seed)),
+            SolverKind::Wfc => Box::new(Wfc::from_rules(grid, rules, seed)),
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            SolverKind::LazyRepair => "Lazy repair",
+            SolverKind::Wfc => "WFC",
+        }
+    }
+}
+
+impl FromStr for SolverKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "lazy" | "lazy_repair" => Ok(SolverKind::LazyRepair),
+            "wfc" => Ok(SolverKind::Wfc),
+            _ => bail!("Unknown solver {s}, expected lazy or wfc"),
+        }
+    }
+}
+
+/// Applies the edits and steps the solver until it stops running or did `max_steps` steps.
+pub fn run_solver(solver: &mut dyn Solver, edits: &[(IVec2, Value)], max_steps: Option<usize>) -> OctaResult<Duration> {
+    let start = Instant::now();
+
+    solver.apply_edits(edits)?;
+    let mut steps = 0;
+    while solver.get_status() == SolveStatus::Running && max_steps.is_none_or(|max| steps < max) {
+        solver.step();
+        steps += 1;
+    }
+
+    Ok(start.elapsed())
+}
+
+impl Solver for GridManager {
+    fn from_rules(grid: Grid, rules: Vec<Rule>, seed: u64) -> Self {
+        GridManager::new(grid, rules).with_seed(seed)
+    }
+
+    fn get_name(&self) -> &'static str {
+        SolverKind::LazyRepair.get_name()
+    }
+
+    fn apply_edits(&mut self, edits: &[(IVec2, Value)]) -> OctaResult<()> {
+        self.select_values(edits)
+    }
+
+    fn step(&mut self) -> bool {
+        self.tick()
+    }
+
+    fn get_result(&self) -> &Grid {
+        &self.grid
+    }
+
+    fn get_status(&self) -> SolveStatus {
+        self.status
+    }
+
+    fn get_stats(&self) -> &SolverStats {
+        &self.stats
+    }
+}
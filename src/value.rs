@@ -1,7 +1,12 @@
+use std::iter;
 use image::Rgba;
+use octa_force::glam::{vec3, Vec3};
 
 pub type ValueNr = u8;
 
+/// `color_index` 0 is reserved for `VALUE_NONE`.
+pub const MAX_VALUES: usize = u8::MAX as usize;
+
 pub const VALUE_NONE: Value = Value {
     color_index: 0,
     debug: 0,
@@ -9,7 +14,7 @@ pub const VALUE_NONE: Value = Value {
     fill2: 0,
 };
 
-#[derive(Default, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Debug)]
+#[derive(Default, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct Value{
     pub color_index: u8,
     debug: u8,
@@ -17,7 +22,7 @@ pub struct Value{
     fill2: u8,
 }
 
-#[derive(Default, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Debug)]
+#[derive(Default, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct ValueColor{
     r: u8, 
     g: u8, 
@@ -51,6 +56,20 @@ impl Value {
             self.debug &= !1;
         }
     }
+    
+    /// Only used to draw locked nodes.
+    pub fn set_locked(&mut self, val: bool) {
+        if val {
+            self.debug |= 2;
+        } else {
+            self.debug &= !2;
+        }
+    }
+}
+
+/// Colors indexed by `color_index` as the renderer reads them, so a placeholder for `VALUE_NONE` comes first.
+pub fn to_value_colors(colors: impl IntoIterator<Item = ValueColor>) -> Vec<ValueColor> {
+    iter::once(ValueColor::new(1, 0, 0)).chain(colors).collect()
 }
 
 impl ValueColor {
@@ -66,4 +85,21 @@ impl ValueColor {
             fill: 0,
         }
     }
+    pub fn to_rgba(&self) -> Rgba<u8> {
+        Rgba([self.r, self.g, self.b, 255])
+    }
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+    pub fn as_vec3(&self) -> Vec3 {
+        vec3(self.r as f32, self.g as f32, self.b as f32)
+    }
+    pub fn from_vec3(v: Vec3) -> Self {
+        let v = v.round().clamp(Vec3::ZERO, Vec3::splat(255.0));
+        ValueColor::new(v.x as u8, v.y as u8, v.z as u8)
+    }
+    pub fn distance_squared(&self, other: &ValueColor) -> u32 {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+        d(self.r, other.r) + d(self.g, other.g) + d(self.b, other.b)
+    }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
-use crate::grid::{Grid};
+use crate::grid::{Grid, GRID_SIZE};
 use crate::util::state_saver::TickType;
+use std::sync::Arc;
 use std::time::Duration;
 use octa_force::gui::Gui;
 use octa_force::anyhow::*;
@@ -9,55 +10,56 @@ use octa_force::egui::FontFamily::Proportional;
 use octa_force::egui::panel::Side;
 use octa_force::egui::TextStyle::{Body, Button, Heading, Monospace, Small};
 use octa_force::egui_winit::winit::event::WindowEvent;
-use octa_force::glam::{ivec2, vec2, Vec2};
-use octa_force::log::info;
+use octa_force::glam::{vec2, IVec2, Vec2};
+use octa_force::log::{error, info};
 use octa_force::puffin_egui::puffin;
 use octa_force::vulkan::ash::vk::AttachmentLoadOp;
-use crate::grid_manager::GridManager;
+use crate::grid_manager::{CandidateOrder, GridManager, SolutionRanking};
 use crate::render::renderer::GridRenderer;
-use crate::render::selector::Selector;
-use crate::rule_gen::gen_rules_from_image;
+use crate::render::selector::{Brush, Selector};
+use crate::score::ScoreStrategy;
+use crate::rule_gen::config::RuleGenConfig;
+use crate::rule_gen::{gen_rules_from_grid, RuleGenSettings};
 use crate::util::state_saver::StateSaver;
-use crate::value::{Value, ValueColor};
+use crate::value::{Value, ValueColor, ValueNr};
+use crate::solver::{Solver, SolverKind};
+use crate::wfc::Wfc;
 
-pub const GRID_SIZE: usize = 32;
+const RULE_GEN_CONFIG_PATH: &str = "rule_gen.cfg";
 
 pub struct Visualization {
     pub gui: Gui,
     
     pub state_saver: StateSaver<GridManager>,
+    /// Edits go to the lazy repair or the classic WFC.
+    solver_kind: SolverKind,
+    wfc: Wfc,
     
     pub grid_renderer: GridRenderer,
     pub selector: Selector,
     
     value_colors: Vec<ValueColor>,
+    rule_gen_settings: RuleGenSettings,
 
     run: bool,
     show_full: bool,
     run_ticks_per_frame: usize,
     pointer_pos_in_grid: Option<Vec2>,
     current_working_grid: Option<usize>,
+    /// Placements of the current brush stroke, they are repaired together when the mouse is released.
+    stroke: Vec<(IVec2, Value)>,
 }
 
 impl Visualization {
     pub fn new(engine: &mut Engine) -> Result<Self> {
         info!("TEST");
         
-        let (rules, value_colors) = gen_rules_from_image(
-            "WaveFunctionCollapse/samples/Hogs.png", 
-            vec![
-                ivec2(-1, -1),
-                ivec2(-1, 0),
-                ivec2(-1, 1),
-                ivec2(0, -1),
-                ivec2(0, 1),
-                ivec2(1, -1),
-                ivec2(1, 0),
-                ivec2(1, 1),
-            ])?;
+        let config = RuleGenConfig::load(RULE_GEN_CONFIG_PATH)?;
+        let (rules, value_colors) = config.gen_rules()?;
         
         let grid = Grid::new(Value::from_value_nr(0));
         
+        let wfc = Wfc::from_rules(grid, rules.to_owned(), fastrand::u64(..));
         let grid_manager = GridManager::new(grid, rules);
         
         let state_saver = StateSaver::from_state(grid_manager, 100);
@@ -78,6 +80,8 @@ impl Visualization {
         
         let v = Visualization {
             state_saver,
+            solver_kind: SolverKind::default(),
+            wfc,
             gui,
             grid_renderer,
             selector,
@@ -86,7 +90,9 @@ impl Visualization {
             run_ticks_per_frame: 10,
             pointer_pos_in_grid: None,
             current_working_grid: None,
+            stroke: vec![],
             value_colors,
+            rule_gen_settings: config.settings,
         };
         //v.place_random_value();
         
@@ -99,13 +105,38 @@ impl Visualization {
         frame_index: usize,
         _delta_time: Duration,
     ) -> Result<()> {
-        if engine.controls.mouse_left && self.selector.selected_pos.is_some() && self.selector.value_type_to_place.is_some() {
-            self.state_saver.get_state_mut().select_value(
-                self.selector.selected_pos.unwrap(),
-                self.selector.value_type_to_place
-            );
+        if engine.controls.mouse_left && self.selector.selected_pos.is_some() {
+            let pos = self.selector.selected_pos.unwrap();
+            let grid_manager = self.state_saver.get_state_mut();
+            
+            match self.selector.brush {
+                Brush::Place => if self.selector.value_type_to_place.is_some() {
+                    let placement = (pos, self.selector.value_type_to_place);
+                    if self.stroke.last() != Some(&placement) {
+                        self.stroke.push(placement);
+                    }
+                }
+                Brush::Lock => grid_manager.lock(pos),
+                Brush::Unlock => grid_manager.unlock(pos),
+            }
         }
         
+        if !engine.controls.mouse_left && !self.stroke.is_empty() {
+            let res = match self.solver_kind {
+                SolverKind::LazyRepair => self.state_saver.get_state_mut().select_values(&self.stroke),
+                SolverKind::Wfc => self.wfc.apply_edits(&self.stroke),
+            };
+            if let Err(err) = res {
+                error!("Placing values failed: {err}");
+            }
+            self.stroke.clear();
+        }
+        
+        if self.run && self.solver_kind == SolverKind::Wfc {
+            for _ in 0..self.run_ticks_per_frame {
+                self.wfc.step();
+            }
+        }
         
         if self.run  {
             
@@ -126,24 +157,30 @@ impl Visualization {
         self.selector.set_selected_pos(self.pointer_pos_in_grid);
         self.grid_renderer.set_selector_pos(self.selector.selected_pos);
         
-        let working_grids = &mut self.state_saver.get_state_mut().working_grids;
+        let working_grids = &self.state_saver.get_state().working_grids;
         if self.current_working_grid.is_some() {
-            
-            if self.show_full {
-                self.grid_renderer.set_chunk_data(&working_grids[self.current_working_grid.unwrap()].full_grid.nodes);
+            let working_grid = &working_grids[self.current_working_grid.unwrap()];
+            let mut grid = self.with_locks(if self.show_full {
+                working_grid.full_grid.to_grid()
             } else {
-                self.grid_renderer.set_chunk_data(&working_grids[self.current_working_grid.unwrap()].empty_grid.nodes);
-            }
+                working_grid.empty_grid.to_grid()
+            });
+            
+            self.grid_renderer.set_chunk_data(&grid.nodes);
 
             self.grid_renderer.update(&mut engine.context, engine.swapchain.format, frame_index);
             
-            if self.show_full {
-                self.selector.clear_from_render_data(&mut working_grids[self.current_working_grid.unwrap()].full_grid);
-            } else {
-                self.selector.clear_from_render_data(&mut working_grids[self.current_working_grid.unwrap()].empty_grid);
-            }
+            self.selector.clear_from_render_data(&mut grid);
+        } else if self.solver_kind == SolverKind::Wfc {
+            let mut grid = *self.wfc.get_result();
+            self.grid_renderer.set_chunk_data(&grid.nodes);
+
+            self.grid_renderer.update(&mut engine.context, engine.swapchain.format, frame_index);
+
+            self.selector.clear_from_render_data(&mut grid);
         } else {
-            self.grid_renderer.set_chunk_data(&self.state_saver.get_state().grid.nodes);
+            let grid = self.with_locks(self.state_saver.get_state().grid);
+            self.grid_renderer.set_chunk_data(&grid.nodes);
 
             self.grid_renderer.update(&mut engine.context, engine.swapchain.format, frame_index);
             
@@ -159,7 +196,16 @@ impl Visualization {
 
         Ok(())
     }
-    
+
+    /// Marks the locked nodes of the repair, so the shader draws them.
+    fn with_locks(&self, mut grid: Grid) -> Grid {
+        let locked = self.state_saver.get_state().get_locked();
+        for (value, _) in grid.nodes.iter_mut().zip(locked).filter(|(_, l)| **l) {
+            value.set_locked(true);
+        }
+        grid
+    }
+
     /*
     fn place_random_value(&mut self) {
         let pos = ivec2(fastrand::i32(0..32), fastrand::i32(0..32));
@@ -238,6 +284,9 @@ impl Visualization {
                         if ui.button(">>>").clicked() {
                             self.run = false;
                             self.state_saver.set_next_tick(TickType::ForwardSave);
+                            if self.solver_kind == SolverKind::Wfc {
+                                self.wfc.step();
+                            }
                         }
                     });
 
@@ -275,14 +324,88 @@ impl Visualization {
 
                         ui.checkbox(&mut self.show_full, "show full");
                     });
+                    
+                    div(ui, |ui| {
+                        if ui.button("learn rules from grid").clicked() {
+                            let grid_manager = self.state_saver.get_state_mut();
+                            match gen_rules_from_grid(&grid_manager.grid, grid_manager.rules.len(), &self.rule_gen_settings) {
+                                Ok(rules) => {
+                                    self.wfc = Wfc::from_rules(grid_manager.grid, rules.to_owned(), grid_manager.get_seed());
+                                    grid_manager.rules = Arc::new(rules);
+                                }
+                                Err(err) => error!("Learning rules from grid failed: {err}"),
+                            }
+                        }
+                    });
+
+                    div(ui, |ui| {
+                        let grid_manager = self.state_saver.get_state_mut();
+                        
+                        let mut score_strategy = grid_manager.get_score_strategy();
+                        egui::ComboBox::from_label("Score")
+                            .selected_text(score_strategy.get_name())
+                            .show_ui(ui, |ui| {
+                                for strategy in ScoreStrategy::ALL {
+                                    ui.selectable_value(&mut score_strategy, strategy, strategy.get_name());
+                                }
+                            });
+                        
+                        if let ScoreStrategy::WeightedAStar { weight } = &mut score_strategy {
+                            ui.add(egui::DragValue::new(weight).speed(0.1).range(0.0..=100.0));
+                        }
+                        
+                        if score_strategy != grid_manager.get_score_strategy() {
+                            grid_manager.set_score_strategy(score_strategy);
+                        }
+                        
+                        ui.checkbox(&mut grid_manager.random_tie_break, "random tie break");
+                        ui.checkbox(&mut grid_manager.incremental, "incremental");
+                        ui.checkbox(&mut grid_manager.diagnose_failures, "diagnose");
+                        
+                        ui.label("Parallel: ");
+                        ui.add(egui::DragValue::new(&mut grid_manager.parallel_expansions).range(1..=256));
+                    });
+                    
+                    div(ui, |ui| {
+                        let grid_manager = self.state_saver.get_state_mut();
+                        
+                        egui::ComboBox::from_label("Candidates")
+                            .selected_text(format!("{:?}", grid_manager.candidate_order))
+                            .show_ui(ui, |ui| {
+                                for order in [CandidateOrder::Learned, CandidateOrder::Shuffled, CandidateOrder::Weighted] {
+                                    ui.selectable_value(&mut grid_manager.candidate_order, order, format!("{order:?}"));
+                                }
+                            });
+                        
+                        ui.label(format!("Seed: {}", grid_manager.get_seed()));
+                        if ui.button("new seed").clicked() {
+                            grid_manager.set_seed(fastrand::u64(..));
+                        }
+                    });
+                    
+                    div(ui, |ui| {
+                        let grid_manager = self.state_saver.get_state_mut();
+                        
+                        ui.label("Solutions: ");
+                        ui.add(egui::DragValue::new(&mut grid_manager.max_solutions).range(1..=32));
+                        
+                        egui::ComboBox::from_label("Ranking")
+                            .selected_text(format!("{:?}", grid_manager.solution_ranking))
+                            .show_ui(ui, |ui| {
+                                for ranking in [SolutionRanking::Score, SolutionRanking::ChangedNodes] {
+                                    ui.selectable_value(&mut grid_manager.solution_ranking, ranking, format!("{ranking:?}"));
+                                }
+                            });
+                    });
 
                     ui.separator();
                     
+                    let num_values = self.state_saver.get_state().rules.len();
                     div(ui, |ui| {
                         ui.label("Place: ");
 
-                        for i in 0..3 {
-                            let value = Value::from_value_nr(i);
+                        for i in 0..num_values {
+                            let value = Value::from_value_nr(i as ValueNr);
                             
                             let mut checked = self.selector.value_type_to_place == value; 
                             ui.checkbox(&mut checked, format!("Value {}", i));
@@ -292,6 +415,21 @@ impl Visualization {
                         }
                         
                     });
+                    
+                    div(ui, |ui| {
+                        ui.label("Brush: ");
+                        ui.radio_value(&mut self.selector.brush, Brush::Place, "place");
+                        ui.radio_value(&mut self.selector.brush, Brush::Lock, "lock");
+                        ui.radio_value(&mut self.selector.brush, Brush::Unlock, "unlock");
+                    });
+                    
+                    div(ui, |ui| {
+                        let grid_manager = self.state_saver.get_state_mut();
+                        ui.label(format!("Locked: {}", grid_manager.get_num_locked()));
+                        if ui.button("unlock all").clicked() {
+                            grid_manager.unlock_all();
+                        }
+                    });
 
                     ui.separator();
 
@@ -303,9 +441,52 @@ impl Visualization {
                         ui.label("Out of bounds");
                     }
 
+                    ui.separator();
+                    
+                    div(ui, |ui| {
+                        egui::ComboBox::from_label("Solver")
+                            .selected_text(self.solver_kind.get_name())
+                            .show_ui(ui, |ui| {
+                                for kind in SolverKind::ALL {
+                                    ui.selectable_value(&mut self.solver_kind, kind, kind.get_name());
+                                }
+                            });
+                    });
+                    
+                    if self.solver_kind == SolverKind::Wfc {
+                        ui.label(format!("Status: {:?}", self.wfc.get_status()));
+                        ui.label(format!("Steps: {}", self.wfc.get_stats().ticks));
+                        ui.label(format!("Restarts: {}", self.wfc.get_stats().restarts));
+                    }
+
                     ui.separator();
                     ui.heading("Working Grids");
+                    ui.label(format!("Status: {:?}", self.state_saver.get_state().status));
+                    if self.state_saver.get_state().is_blocked_by_locks() {
+                        let stats = &self.state_saver.get_state().stats;
+                        ui.label(format!("Locked nodes may make this edit unsolvable: {} branches died on them, {} were dropped by new locks",
+                                         stats.lock_conflicts, stats.dropped_by_locks));
+                    }
+                    if let Some(diagnosis) = &self.state_saver.get_state().diagnosis {
+                        ui.label(diagnosis.to_string());
+                    }
                     ui.label(format!("Active: {}", self.state_saver.get_state().working_grids.len()));
+                    
+                    let stats = &self.state_saver.get_state().stats;
+                    ui.label(format!("Ticks: {}", stats.ticks));
+                    ui.label(format!("Pruned: {} by beam, {} by queue size", stats.pruned_by_beam, stats.pruned_by_queue));
+                    
+                    let num_solutions = self.state_saver.get_state().done_grids.len();
+                    if num_solutions > 1 {
+                        div(ui, |ui| {
+                            ui.label("Solution: ");
+                            for i in 0..num_solutions {
+                                if ui.button(format!("{i}")).clicked() {
+                                    self.state_saver.get_state_mut().select_solution(i);
+                                }
+                            }
+                        });
+                    }
                 });
             });
             
@@ -325,14 +506,14 @@ impl Visualization {
                             let response = if self.current_working_grid == Some(i) {
                                 ui.heading(
                                     format!("{i}: orders: {} ({}, {}, {})", 
-                                            working_grid.get_score(), 
+                                            working_grid.score, 
                                             working_grid.orders.len(), 
                                             working_grid.satisfied_count,
                                             working_grid.set_count
                                     ))
                             } else {
                                 ui.label(format!("{i}: orders: {} ({}, {}, {})",
-                                                 working_grid.get_score(),
+                                                 working_grid.score,
                                                  working_grid.orders.len(),
                                                  working_grid.satisfied_count,
                                                  working_grid.set_count
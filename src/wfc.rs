@@ -0,0 +1,299 @@
+use fastrand::Rng;
+use octa_force::anyhow::bail;
+use octa_force::glam::IVec2;
+use octa_force::OctaResult;
+use crate::grid::{get_node_index_from_pos, get_pos_in_chunk_from_node_index, is_pos_in_grid, Grid, NodeIndex, NODES_PER_GRID};
+use crate::grid_manager::{SolveStatus, SolverStats};
+use crate::rules::Rule;
+use crate::solver::{Solver, SolverKind};
+use crate::value::{Value, ValueNr};
+
+const WFC_MAX_RESTARTS: usize = 100;
+
+/// Classic WaveFunctionCollapse on the rules of the lazy repair. Every edit regenerates the whole grid with all edits fixed.
+///
+/// Two values are compatible at an offset if a req of each allows the other at that offset.
+/// A value without any req at an offset allows everything there.
+/// Every step collapses the node with the lowest entropy and propagates the removed values.
+/// A contradiction restarts from the edits.
+#[derive(Clone)]
+pub struct Wfc {
+    grid: Grid,
+    num_values: usize,
+    offsets: Vec<IVec2>,
+    /// `compatible[(value * offsets + offset) * num_values + other]` is true if `other` may be at `offset` from `value`.
+    compatible: Vec<bool>,
+    /// Value weights of the rules, values that were never learned are only placed by edits.
+    weights: Vec<f32>,
+    /// Possible values, `wave[node_index * num_values + value]`.
+    wave: Vec<bool>,
+    num_possible: Vec<usize>,
+
+    edits: Vec<(IVec2, Value)>,
+    pub max_restarts: usize,
+
+    stats: SolverStats,
+    status: SolveStatus,
+    seed: u64,
+    rng: Rng,
+}
+
+impl Wfc {
+    pub fn new(grid: Grid, rules: &[Rule], seed: u64) -> Self {
+        let num_values = rules.len();
+
+        let mut offsets: Vec<IVec2> = rules.iter()
+            .flat_map(|rule| rule.get_reqs().iter())
+            .flat_map(|req| req.reqs.iter().map(|(offset, _)| *offset))
+            .collect();
+        offsets.sort_by_key(|offset| offset.to_array());
+        offsets.dedup();
+        let num_offsets = offsets.len();
+
+        let mut has_reqs = vec![false; num_values * num_offsets];
+        let mut allows = vec![false; num_values * num_offsets * num_values];
+        for rule in rules.iter() {
+            let value = rule.value.get_value_nr() as usize;
+            for (offset, other) in rule.get_reqs().iter().flat_map(|req| req.reqs.iter()) {
+                let offset_index = offsets.binary_search_by_key(&offset.to_array(), |o| o.to_array()).unwrap();
+                has_reqs[value * num_offsets + offset_index] = true;
+                if other.is_some() && (other.get_value_nr() as usize) < num_values {
+                    allows[(value * num_offsets + offset_index) * num_values + other.get_value_nr() as usize] = true;
+                }
+            }
+        }
+
+        let allowed = |value: usize, offset_index: usize, other: usize| {
+            !has_reqs[value * num_offsets + offset_index] || allows[(value * num_offsets + offset_index) * num_values + other]
+        };
+
+        let mut compatible = vec![false; num_values * num_offsets * num_values];
+        for (offset_index, offset) in offsets.iter().enumerate() {
+            let opposite_index = offsets.binary_search_by_key(&(-*offset).to_array(), |o| o.to_array()).ok();
+            for value in 0..num_values {
+                for other in 0..num_values {
+                    compatible[(value * num_offsets + offset_index) * num_values + other] = allowed(value, offset_index, other)
+                        && opposite_index.is_none_or(|opposite_index| allowed(other, opposite_index, value));
+                }
+            }
+        }
+
+        let weights = rules.iter()
+            .map(|rule| rule.get_value_weight())
+            .collect();
+
+        Wfc {
+            grid,
+            num_values,
+            offsets,
+            compatible,
+            weights,
+            wave: vec![],
+            num_possible: vec![],
+            edits: vec![],
+            max_restarts: WFC_MAX_RESTARTS,
+            stats: SolverStats::default(),
+            status: SolveStatus::default(),
+            seed,
+            rng: Rng::with_seed(seed),
+        }
+    }
+
+    /// Resets the wave to the edits. Returns false if the edits contradict each other.
+    fn restart(&mut self) -> bool {
+        self.wave = (0..NODES_PER_GRID)
+            .flat_map(|_| self.weights.iter().map(|weight| *weight > 0.0))
+            .collect();
+        let num_learned = self.weights.iter().filter(|weight| **weight > 0.0).count();
+        self.num_possible = vec![num_learned; NODES_PER_GRID];
+
+        let mut changed = vec![];
+        for (pos, value) in self.edits.iter() {
+            let node_index = get_node_index_from_pos(*pos);
+            let nr = value.get_value_nr() as usize;
+            for other in 0..self.num_values {
+                self.wave[node_index * self.num_values + other] = other == nr;
+            }
+            self.num_possible[node_index] = 1;
+            changed.push(node_index);
+        }
+
+        if self.num_possible.contains(&0) {
+            return false
+        }
+
+        self.propagate(changed)
+    }
+
+    /// Removes values that lost all support of a changed neighbor. Returns false on a contradiction.
+    fn propagate(&mut self, mut changed: Vec<NodeIndex>) -> bool {
+        let num_values = self.num_values;
+        let num_offsets = self.offsets.len();
+
+        while let Some(node_index) = changed.pop() {
+            let pos = get_pos_in_chunk_from_node_index(node_index);
+
+            for (offset_index, offset) in self.offsets.iter().enumerate() {
+                let other_pos = pos + *offset;
+                if !is_pos_in_grid(other_pos) {
+                    continue
+                }
+
+                let other_index = get_node_index_from_pos(other_pos);
+                let mut removed = false;
+                for other in 0..num_values {
+                    if !self.wave[other_index * num_values + other] {
+                        continue
+                    }
+
+                    let supported = (0..num_values).any(|value| {
+                        self.wave[node_index * num_values + value]
+                            && self.compatible[(value * num_offsets + offset_index) * num_values + other]
+                    });
+                    if !supported {
+                        self.wave[other_index * num_values + other] = false;
+                        self.num_possible[other_index] -= 1;
+                        removed = true;
+                    }
+                }
+
+                if self.num_possible[other_index] == 0 {
+                    return false
+                }
+
+                if removed {
+                    changed.push(other_index);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Undecided node with the lowest entropy, with a little noise to break ties.
+    fn observe(&mut self) -> Option<NodeIndex> {
+        let mut best = None;
+        let mut best_entropy = f32::MAX;
+        for node_index in 0..NODES_PER_GRID {
+            if self.num_possible[node_index] <= 1 {
+                continue
+            }
+
+            let (sum, sum_log) = self.get_possible(node_index)
+                .map(|value| self.weights[value])
+                .fold((0.0, 0.0), |(sum, sum_log), weight| (sum + weight, sum_log + weight * weight.ln()));
+            let entropy = sum.ln() - sum_log / sum + self.rng.f32() * 1e-6;
+
+            if entropy < best_entropy {
+                best_entropy = entropy;
+                best = Some(node_index);
+            }
+        }
+
+        best
+    }
+
+    fn collapse(&mut self, node_index: NodeIndex) {
+        let possible: Vec<_> = self.get_possible(node_index).collect();
+        let sum: f32 = possible.iter().map(|value| self.weights[*value]).sum();
+
+        let mut r = self.rng.f32() * sum;
+        let mut chosen = possible[possible.len() - 1];
+        for value in possible {
+            r -= self.weights[value];
+            if r < 0.0 {
+                chosen = value;
+                break
+            }
+        }
+
+        for value in 0..self.num_values {
+            self.wave[node_index * self.num_values + value] = value == chosen;
+        }
+        self.num_possible[node_index] = 1;
+    }
+
+    fn get_possible(&self, node_index: NodeIndex) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_values).filter(move |value| self.wave[node_index * self.num_values + value])
+    }
+
+    fn contradiction(&mut self) {
+        self.stats.restarts += 1;
+        if self.stats.restarts > self.max_restarts || !self.restart() {
+            self.status = SolveStatus::Unsolvable;
+        }
+    }
+}
+
+impl Solver for Wfc {
+    fn from_rules(grid: Grid, rules: Vec<Rule>, seed: u64) -> Self {
+        Wfc::new(grid, &rules, seed)
+    }
+
+    fn get_name(&self) -> &'static str {
+        SolverKind::Wfc.get_name()
+    }
+
+    /// Adds the edits to the ones placed before and regenerates the grid. Later edits on the same node win.
+    fn apply_edits(&mut self, edits: &[(IVec2, Value)]) -> OctaResult<()> {
+        for (pos, value) in edits.iter() {
+            if value.is_none() {
+                bail!("Can not place an empty value at {pos}");
+            }
+            if value.get_value_nr() as usize >= self.num_values {
+                bail!("Value {} at {pos} has no rule, there are only {} values", value.get_value_nr(), self.num_values);
+            }
+        }
+        
+        for (pos, value) in edits.iter() {
+            if !is_pos_in_grid(*pos) {
+                continue
+            }
+
+            self.edits.retain(|(p, _)| p != pos);
+            self.edits.push((*pos, *value));
+        }
+
+        self.stats = SolverStats::default();
+        self.rng = Rng::with_seed(self.seed);
+        self.status = if self.restart() { SolveStatus::Running } else { SolveStatus::Unsolvable };
+        Ok(())
+    }
+
+    fn step(&mut self) -> bool {
+        if self.status != SolveStatus::Running {
+            return false
+        }
+
+        self.stats.ticks += 1;
+        match self.observe() {
+            Some(node_index) => {
+                self.collapse(node_index);
+                if !self.propagate(vec![node_index]) {
+                    self.contradiction();
+                }
+            }
+            None => {
+                for node_index in 0..NODES_PER_GRID {
+                    let value = self.get_possible(node_index).next().unwrap();
+                    self.grid.nodes[node_index] = Value::from_value_nr(value as ValueNr);
+                }
+                self.status = SolveStatus::Solved;
+            }
+        }
+
+        true
+    }
+
+    fn get_result(&self) -> &Grid {
+        &self.grid
+    }
+
+    fn get_status(&self) -> SolveStatus {
+        self.status
+    }
+
+    fn get_stats(&self) -> &SolverStats {
+        &self.stats
+    }
+}
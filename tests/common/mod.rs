@@ -0,0 +1,12 @@
+//! Setup shared by the tests.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Own folder per test in the temp dir, tests run in parallel.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("reload_{name}_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use reload::rule_gen::overlapping::{OverlappingModel, OverlappingSettings};
+use common::temp_dir;
+
+#[test]
+fn pattern_reqs_are_weighted_by_the_neighbor_pattern() {
+    // Stripes with one wide band, so the patterns are seen a different number of times.
+    let image = RgbaImage::from_fn(6, 6, |x, _| if x < 4 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) });
+    let path = temp_dir("overlapping_weights").join("stripes.png");
+    image.save(&path).unwrap();
+
+    let mut settings = OverlappingSettings::new(2);
+    settings.symmetry = 1;
+    let model = OverlappingModel::from_image(path.to_str().unwrap(), &settings).unwrap();
+
+    let value_weights: Vec<_> = model.rules.iter().map(|rule| rule.get_value_weight()).collect();
+    assert_eq!(value_weights.iter().sum::<f32>(), 36.0);
+    assert!(value_weights.iter().any(|w| *w != value_weights[0]), "{value_weights:?}");
+
+    for rule in model.rules.iter() {
+        assert!(!rule.get_reqs().is_empty());
+        for (req, weight) in rule.get_reqs().iter().zip(rule.get_weights()) {
+            assert_eq!(*weight, value_weights[req.reqs[0].1.get_value_nr() as usize]);
+        }
+    }
+}
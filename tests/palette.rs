@@ -0,0 +1,87 @@
+mod common;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use reload::rule_gen::neighborhood::Neighborhood;
+use reload::rule_gen::palette::{build_palette, PaletteMode};
+use reload::rule_gen::{gen_rules_from_images, RuleGenSample, RuleGenSettings, SampleBorder};
+use reload::value::ValueColor;
+use common::temp_dir;
+
+fn no_holes(_: Rgba<u8>) -> bool {
+    false
+}
+
+fn image_of(colors: &[[u8; 3]]) -> DynamicImage {
+    let image = RgbaImage::from_fn(colors.len() as u32, 1, |x, _| {
+        let [r, g, b] = colors[x as usize];
+        Rgba([r, g, b, 255])
+    });
+    DynamicImage::ImageRgba8(image)
+}
+
+#[test]
+fn exact_and_threshold_palettes() {
+    let images = [image_of(&[[0, 0, 255], [250, 0, 0], [0, 0, 255], [255, 0, 0]])];
+
+    let exact = build_palette(&images, &PaletteMode::Exact, &no_holes).unwrap();
+    assert_eq!(exact, [ValueColor::new(0, 0, 255), ValueColor::new(250, 0, 0), ValueColor::new(255, 0, 0)]);
+
+    let threshold = build_palette(&images, &PaletteMode::Threshold(10.0), &no_holes).unwrap();
+    assert_eq!(threshold, [ValueColor::new(0, 0, 255), ValueColor::new(250, 0, 0)]);
+}
+
+#[test]
+fn reduced_palettes_find_the_color_clusters() {
+    let images = [image_of(&[[250, 0, 0], [240, 10, 0], [250, 0, 0], [0, 0, 250], [10, 0, 240], [0, 0, 250]])];
+    let is_near = |palette: &[ValueColor], color: ValueColor| palette.iter().any(|c| c.distance_squared(&color) < 20 * 20);
+
+    for mode in [PaletteMode::KMeans(2), PaletteMode::MedianCut(2)] {
+        let palette = build_palette(&images, &mode, &no_holes).unwrap();
+        assert_eq!(palette.len(), 2, "{mode:?}");
+        assert!(is_near(&palette, ValueColor::new(250, 0, 0)), "{mode:?} {palette:?}");
+        assert!(is_near(&palette, ValueColor::new(0, 0, 250)), "{mode:?} {palette:?}");
+    }
+
+    // Asking for more entries than colors keeps every color.
+    let palette = build_palette(&images, &PaletteMode::KMeans(8), &no_holes).unwrap();
+    assert_eq!(palette.len(), 4);
+}
+
+#[test]
+fn fixed_palette_snaps_samples() {
+    let fixed = vec![ValueColor::new(0, 0, 0), ValueColor::new(255, 255, 255)];
+    let image = RgbaImage::from_fn(8, 8, |x, y| if (x + y) % 2 == 0 { Rgba([20, 10, 30, 255]) } else { Rgba([230, 240, 250, 255]) });
+    let path = temp_dir("fixed_palette").join("checker.png");
+    image.save(&path).unwrap();
+
+    let settings = RuleGenSettings::new(Neighborhood::Moore.get_offsets())
+        .with_border(SampleBorder::Wrap)
+        .with_palette(PaletteMode::Fixed(fixed.to_owned()));
+    let (rules, value_colors) = gen_rules_from_images(&[RuleGenSample::new(path.to_str().unwrap())], &settings).unwrap();
+
+    assert_eq!(value_colors[1..], fixed);
+    assert_eq!(rules.len(), 2);
+    for (value_nr, rule) in rules.iter().enumerate() {
+        // A checkerboard has a single neighborhood per value, the other color on the sides and its own on the diagonals.
+        assert_eq!(rule.get_reqs().len(), 1);
+        for (offset, value) in rule.get_reqs()[0].reqs.iter() {
+            let same = (offset.x + offset.y) % 2 == 0;
+            assert_eq!(value.get_value_nr() as usize == value_nr, same, "{offset}");
+        }
+    }
+}
+
+#[test]
+fn invalid_palettes_fail() {
+    let images = [image_of(&[[255, 0, 0], [0, 0, 255]])];
+    for mode in [PaletteMode::KMeans(0), PaletteMode::MedianCut(0)] {
+        let err = build_palette(&images, &mode, &no_holes).err().unwrap();
+        assert!(err.to_string().contains("at least 1"), "{mode:?}: {err}");
+    }
+
+    let all_holes = |_: Rgba<u8>| true;
+    for mode in [PaletteMode::Exact, PaletteMode::KMeans(2), PaletteMode::MedianCut(2)] {
+        let err = build_palette(&images, &mode, &all_holes).err().unwrap();
+        assert!(err.to_string().contains("Palette is empty"), "{mode:?}: {err}");
+    }
+}
@@ -0,0 +1,33 @@
+#[path = "../benches/common/mod.rs"]
+mod common;
+
+use reload::grid_manager::{GridManager, SolveStatus};
+use common::{pattern_grid, pattern_rules, random_edits};
+
+fn repair(num_threads: usize) -> GridManager {
+    let grid = pattern_grid();
+    let rules = pattern_rules(&grid);
+
+    let mut grid_manager = GridManager::new(grid, rules).with_seed(7);
+    grid_manager.parallel_expansions = 16;
+    grid_manager.num_threads = Some(num_threads);
+    grid_manager.select_values(&random_edits(&grid, 3, 7)).unwrap();
+    while grid_manager.status == SolveStatus::Running {
+        grid_manager.tick();
+    }
+
+    grid_manager
+}
+
+#[test]
+fn parallel_repair_does_not_depend_on_thread_count() {
+    let single = repair(1);
+    assert_eq!(single.status, SolveStatus::Solved);
+
+    for num_threads in [2, 3, 8] {
+        let multi = repair(num_threads);
+        assert_eq!(multi.status, single.status);
+        assert_eq!(multi.stats.ticks, single.stats.ticks, "{num_threads} threads");
+        assert!(multi.grid.nodes == single.grid.nodes, "{num_threads} threads");
+    }
+}
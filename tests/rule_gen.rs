@@ -0,0 +1,40 @@
+mod common;
+
+use image::{Rgba, RgbaImage};
+use octa_force::glam::{ivec2, IVec2};
+use reload::grid::{get_node_index_from_pos, Grid, GRID_SIZE};
+use reload::rule_gen::neighborhood::Neighborhood;
+use reload::rule_gen::{gen_rules_from_grid, gen_rules_from_images, RuleGenSample, RuleGenSettings, SampleBorder};
+use reload::value::{Value, ValueColor, ValueNr, VALUE_NONE};
+use common::temp_dir;
+
+#[test]
+fn rules_from_grid_match_rules_from_image() {
+    let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+    let value_at = |pos: IVec2| ((pos.x / 2 + pos.y) % 3 + (pos.x * pos.y) % 5 / 4) as usize % 3;
+
+    let image = RgbaImage::from_fn(GRID_SIZE as u32, GRID_SIZE as u32, |x, y| {
+        let [r, g, b] = colors[value_at(ivec2(x as i32, y as i32))];
+        Rgba([r, g, b, 255])
+    });
+    let path = temp_dir("grid_vs_image").join("pattern.png");
+    image.save(&path).unwrap();
+
+    let settings = RuleGenSettings::new(Neighborhood::Moore.get_offsets()).with_border(SampleBorder::Wrap);
+    let (image_rules, value_colors) = gen_rules_from_images(&[RuleGenSample::new(path.to_str().unwrap())], &settings).unwrap();
+
+    // The palette is in order of first appearance, so the value numbers of the grid are looked up in it.
+    let mut grid = Grid::new(VALUE_NONE);
+    for x in 0..GRID_SIZE as i32 {
+        for y in 0..GRID_SIZE as i32 {
+            let [r, g, b] = colors[value_at(ivec2(x, y))];
+            let value_nr = value_colors[1..].iter().position(|c| *c == ValueColor::new(r, g, b)).unwrap();
+            grid.nodes[get_node_index_from_pos(ivec2(x, y))] = Value::from_value_nr(value_nr as ValueNr);
+        }
+    }
+    let grid_rules = gen_rules_from_grid(&grid, value_colors.len() - 1, &settings).unwrap();
+
+    assert_eq!(image_rules.len(), colors.len());
+    assert!(image_rules.iter().all(|rule| !rule.get_reqs().is_empty()));
+    assert_eq!(grid_rules, image_rules);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::fs;
+use image::{imageops, Rgba, RgbaImage};
+use octa_force::glam::{ivec2, IVec2};
+use reload::rule_gen::tiled::TiledModel;
+use reload::value::ValueNr;
+use common::temp_dir;
+
+fn write_tileset(name: &str, tiles: &str, neighbors: &str) -> String {
+    let dir = temp_dir(name);
+    for (i, tile) in ["line", "corner", "tee", "diagonal", "cross"].iter().enumerate() {
+        let image = RgbaImage::from_fn(2, 2, |x, y| Rgba([(x * 100) as u8, (y * 100) as u8, i as u8 * 50, 255]));
+        image.save(dir.join(format!("{tile}.png"))).unwrap();
+    }
+
+    let path = dir.join("data.xml");
+    fs::write(&path, format!("<set><tiles>{tiles}</tiles><neighbors>{neighbors}</neighbors></set>")).unwrap();
+    path.to_str().unwrap().to_owned()
+}
+
+#[test]
+fn tile_symmetries_create_their_variants() {
+    let path = write_tileset("symmetries", r#"
+        <tile name="line" symmetry="I"/>
+        <tile name="corner" symmetry="L"/>
+        <tile name="tee" symmetry="T"/>
+        <tile name="diagonal" symmetry="\"/>
+        <tile name="cross" symmetry="X"/>
+    "#, "");
+    let model = TiledModel::from_xml(&path).unwrap();
+
+    let counts: Vec<_> = ["line", "corner", "tee", "diagonal", "cross"].iter()
+        .map(|tile| model.tile_names.iter().filter(|n| n.split(' ').next() == Some(*tile)).count())
+        .collect();
+    assert_eq!(counts, [2, 4, 4, 2, 1]);
+    assert_eq!(model.rules.len(), 13);
+
+    // Every variant is the one before it rotated by 90 degrees.
+    for t in 1..4 {
+        assert_eq!(model.tile_images[2 + t], imageops::rotate270(&model.tile_images[2 + t - 1]));
+    }
+}
+
+#[test]
+fn tile_neighbors_follow_rotation() {
+    let path = write_tileset("neighbors", r#"<tile name="line" symmetry="I" weight="2"/>"#,
+        r#"<neighbor left="line" right="line"/>"#);
+    let model = TiledModel::from_xml(&path).unwrap();
+
+    let req_offsets = |value_nr: usize| -> Vec<(IVec2, ValueNr)> {
+        model.rules[value_nr].get_reqs().iter()
+            .map(|req| (req.reqs[0].0, req.reqs[0].1.get_value_nr()))
+            .collect()
+    };
+
+    // The horizontal line only continues to the sides and the rotated one only up and down.
+    assert_eq!(req_offsets(0), [(ivec2(-1, 0), 0), (ivec2(1, 0), 0)]);
+    assert_eq!(req_offsets(1), [(ivec2(0, 1), 1), (ivec2(0, -1), 1)]);
+    assert!(model.rules.iter().all(|rule| rule.is_pairwise()));
+}
+
+#[test]
+fn tile_reqs_are_weighted_by_the_neighbor_tile() {
+    let path = write_tileset("weights", r#"<tile name="line" symmetry="I" weight="2"/><tile name="cross" weight="6"/>"#,
+        r#"<neighbor left="cross" right="line"/><neighbor left="cross" right="cross"/>"#);
+    let model = TiledModel::from_xml(&path).unwrap();
+
+    for (value_nr, rule) in model.rules.iter().enumerate() {
+        assert_eq!(rule.get_value_weight(), model.weights[value_nr]);
+        for (req, weight) in rule.get_reqs().iter().zip(rule.get_weights()) {
+            assert_eq!(*weight, model.weights[req.reqs[0].1.get_value_nr() as usize]);
+        }
+    }
+    
+    // The cross allows both tiles as neighbors, so weighted candidates prefer one over the other.
+    let cross_weights = model.rules[2].get_weights();
+    assert!(cross_weights.contains(&2.0) && cross_weights.contains(&6.0), "{cross_weights:?}");
+}
+
+#[test]
+fn unknown_tile_symmetry_fails() {
+    let path = write_tileset("unknown_symmetry", r#"<tile name="line" symmetry="Q"/>"#, "");
+    let err = TiledModel::from_xml(&path).err().unwrap();
+    assert!(err.to_string().contains("Unknown tile symmetry Q"), "{err}");
+}